@@ -1,68 +1,388 @@
-use crate::editor::Dimensions;
-use sdl2::{pixels::Color, rect::Rect, surface::SurfaceRef};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    surface::{Surface, SurfaceRef},
+    ttf::{Font, FontStyle},
+};
+
+// src is in physical pixels of the atlas surface, the metrics are in logical
+// pixels so layout doesn't depend on the display scale
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub src: Rect,
+    pub advance: i32,
+    pub bearing_x: i32,
+    pub bearing_y: i32,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Style {
+    #[default]
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl Style {
+    const ALL: [Style; 4] = [
+        Style::Regular,
+        Style::Bold,
+        Style::Italic,
+        Style::BoldItalic,
+    ];
+
+    fn font_style(self) -> FontStyle {
+        match self {
+            Style::Regular => FontStyle::NORMAL,
+            Style::Bold => FontStyle::BOLD,
+            Style::Italic => FontStyle::ITALIC,
+            Style::BoldItalic => FontStyle::BOLD | FontStyle::ITALIC,
+        }
+    }
+}
+
+// a piece of a laid out line, either a single character or a ligature
+// cluster covering `len` characters starting at `column`. Tabs only take up
+// space up to the next tab stop and have no glyph to draw
+#[derive(Debug, Clone, Copy)]
+pub struct Shaped {
+    pub column: usize,
+    pub len: usize,
+    pub x: i32,
+    pub advance: i32,
+    pub glyph: Option<Glyph>,
+}
 
 pub struct Atlas {
-    glyphs: Vec<Rect>,
-    font_size: Dimensions,
+    // unique per atlas so a renderer can tell when its cached texture is stale
+    id: u32,
+    surface: Surface<'static>,
+    // one block of NUM_GLYPHS entries per style, in the order of Style::ALL
+    glyphs: Vec<Glyph>,
+    // pre-shaped ligature clusters, only filled when ligatures are enabled
+    clusters: HashMap<(String, Style), Glyph>,
+    line_height: u32,
+    scale: f32,
+    // tab stops are this many spaces apart
+    tab_width: usize,
 }
 
+pub const DEFAULT_FONT: &str = "FiraCode-VariableFont_wght.ttf";
+
+static NEXT_ATLAS_ID: AtomicU32 = AtomicU32::new(0);
+
 const NUM_GLYPHS: usize = 128;
-const FONT_TEXTURE_SIZE: u32 = 512;
+// the atlas starts at the smaller size and doubles until everything fits,
+// the larger is as big as textures can be counted on to go
+const MIN_TEXTURE_SIZE: u32 = 256;
+const MAX_TEXTURE_SIZE: u32 = 8192;
+const FALLBACK_GLYPH: char = '?';
+// sequences FiraCode substitutes with a ligature, these get rendered as a
+// whole run so SDL_ttf's HarfBuzz shaping picks the ligature glyph
+const LIGATURES: [&str; 40] = [
+    "->", "=>", "<-", "<=", ">=", "==", "!=", "===", "!==", "::", ":::", ":=", "&&", "||", "++",
+    "--", "**", "//", "///", "/*", "*/", "..", "...", "..=", "|>", "<|", "<>", "<=>", "<<", ">>",
+    "##", "#{", "#[", "~>", "<~", "=~", "!~", "??", "?.", "-->",
+];
+const MAX_LIGATURE_LEN: usize = 3;
 
 impl Atlas {
-    pub fn new<S>(expected_font_size: u16, surface: &mut S) -> Result<Atlas, String>
+    // glyphs are rasterized at expected_font_size * scale so they stay crisp on
+    // high density displays where one logical pixel covers several physical ones
+    pub fn new(
+        font_path: &Path,
+        expected_font_size: u16,
+        ligatures: bool,
+        scale: f32,
+    ) -> Result<Atlas, String> {
+        let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+        let mut font = ttf_context
+            .load_font(
+                font_path,
+                (expected_font_size as f32 * scale).round() as u16,
+            )
+            .map_err(|e| format!("could not load font {}: {}", font_path.display(), e))?;
+
+        let to_logical = |pixels: i32| (pixels as f32 / scale).round() as i32;
+        let line_height = font.height() as u32;
+
+        // everything is rendered before the atlas is made so it can be made
+        // big enough to hold it all
+        let mut rendered = Vec::with_capacity(NUM_GLYPHS * Style::ALL.len());
+        for style in Style::ALL {
+            font.set_style(style.font_style());
+            for i in 32u8..127 {
+                let metrics = font
+                    .find_glyph_metrics(i as char)
+                    .ok_or(format!("Font has no glyph for {:?}", i as char))?;
+                let text = Self::render(&font, &(i as char).to_string())?;
+                rendered.push((Self::glyph_index(i as char, style), metrics, text));
+            }
+        }
+        // ligature clusters share the atlas with the glyphs
+        let mut rendered_clusters = Vec::new();
+        let ligature_styles = if ligatures { &Style::ALL[..] } else { &[] };
+        for &style in ligature_styles {
+            font.set_style(style.font_style());
+            for sequence in LIGATURES {
+                rendered_clusters.push((sequence, style, Self::render(&font, sequence)?));
+            }
+        }
+        let sizes: Vec<(u32, u32)> = rendered
+            .iter()
+            .map(|(_, _, text)| text)
+            .chain(rendered_clusters.iter().map(|(_, _, text)| text))
+            .map(|text| (text.width(), text.height()))
+            .collect();
+        let texture_size = Self::texture_size(&sizes, line_height).ok_or(format!(
+            "font size {expected_font_size} is too large to fit its glyphs in a texture"
+        ))?;
+        let masks = PixelFormatEnum::RGB24.into_masks()?;
+        let mut surface = Surface::from_pixelmasks(texture_size, texture_size, &masks)?;
+        surface.set_color_key(true, Color::BLACK)?;
+
+        // stores all the ASCII char positions and metrics for every style
+        let mut glyphs: Vec<Glyph> = vec![
+            Glyph {
+                src: Rect::new(0, 0, 0, 0),
+                advance: 0,
+                bearing_x: 0,
+                bearing_y: 0,
+            };
+            NUM_GLYPHS * Style::ALL.len()
+        ];
+
+        let mut clusters = HashMap::new();
+
+        let mut position = (0, 0);
+        for (index, metrics, text) in &rendered {
+            let src = Self::pack(text, &mut surface, &mut position, line_height)?;
+            glyphs[*index] = Glyph {
+                src,
+                advance: to_logical(metrics.advance),
+                bearing_x: to_logical(metrics.minx),
+                bearing_y: to_logical(metrics.maxy),
+            };
+        }
+
+        for &(sequence, style, ref text) in &rendered_clusters {
+            let src = Self::pack(text, &mut surface, &mut position, line_height)?;
+            let first = glyphs[Self::glyph_index(sequence.chars().next().unwrap(), style)];
+            let advance = sequence
+                .chars()
+                .map(|c| glyphs[Self::glyph_index(c, style)].advance)
+                .sum();
+            clusters.insert(
+                (sequence.to_string(), style),
+                Glyph {
+                    src,
+                    advance,
+                    bearing_x: first.bearing_x,
+                    bearing_y: first.bearing_y,
+                },
+            );
+        }
+
+        Ok(Atlas {
+            id: NEXT_ATLAS_ID.fetch_add(1, Ordering::Relaxed),
+            surface,
+            glyphs,
+            clusters,
+            line_height: to_logical(line_height as i32) as u32,
+            scale,
+            tab_width: 4,
+        })
+    }
+
+    fn render(font: &Font, text: &str) -> Result<Surface<'static>, String> {
+        font.render(text)
+            .blended(Color::RGBA(255, 255, 255, 0))
+            .map_err(|e| e.to_string())
+    }
+
+    // the smallest atlas, in powers of two, that everything fits in
+    fn texture_size(sizes: &[(u32, u32)], line_height: u32) -> Option<u32> {
+        let mut texture_size = MIN_TEXTURE_SIZE;
+        loop {
+            let mut position = (0, 0);
+            if sizes.iter().all(|(width, height)| {
+                Self::place(&mut position, *width, *height, line_height, texture_size).is_some()
+            }) {
+                return Some(texture_size);
+            }
+            if texture_size >= MAX_TEXTURE_SIZE {
+                return None;
+            }
+            texture_size *= 2;
+        }
+    }
+
+    // where the next piece goes in rows of line_height, None once it's full
+    fn place(
+        position: &mut (u32, u32),
+        width: u32,
+        height: u32,
+        line_height: u32,
+        texture_size: u32,
+    ) -> Option<Rect> {
+        if position.0 + width >= texture_size {
+            *position = (0, position.1 + line_height + 1);
+        }
+        if position.1 + height >= texture_size {
+            return None;
+        }
+        let src = Rect::new(position.0 as i32, position.1 as i32, width, height);
+        position.0 += width;
+        Some(src)
+    }
+
+    // copies the rendered text to the next free spot in the atlas and
+    // returns where it went
+    fn pack(
+        text: &SurfaceRef,
+        surface: &mut SurfaceRef,
+        position: &mut (u32, u32),
+        line_height: u32,
+    ) -> Result<Rect, String> {
+        let src = Self::place(
+            position,
+            text.width(),
+            text.height(),
+            line_height,
+            surface.width(),
+        )
+        .ok_or("ran out of atlas space for glyphs")?;
+        text.blit(None, surface, src)?;
+        Ok(src)
+    }
+
+    pub fn get_glyph(&self, character: &char, style: Style) -> &Glyph {
+        if (*character as usize) < NUM_GLYPHS {
+            let glyph = &self.glyphs[Self::glyph_index(*character, style)];
+            if glyph.src.width() > 0 {
+                return glyph;
+            }
+        }
+        &self.glyphs[Self::glyph_index(FALLBACK_GLYPH, style)]
+    }
+
+    pub fn get_line_height(&self) -> u32 {
+        self.line_height
+    }
+
+    pub fn get_scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn surface(&self) -> &SurfaceRef {
+        &self.surface
+    }
+
+    // splits the line into what actually gets drawn, joining runs of the same
+    // style into ligature clusters when the atlas has them
+    pub fn shape_line<F>(&self, line: &str, style_at: F) -> Vec<Shaped>
     where
-        S: AsMut<SurfaceRef>,
+        F: Fn(usize) -> Style,
     {
-        let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
-        let font = ttf_context.load_font("FiraCode-VariableFont_wght.ttf", expected_font_size)?;
-
-        let char_size: (u32, u32) = font
-            .size_of_latin1(&[32 as u8])
-            .map_err(|e| e.to_string())?;
-        let font_size = Dimensions {
-            height: char_size.1,
-            width: char_size.0,
-        };
-        // stores all the ASCII char positions
-        let mut glyphs: Vec<Rect> = vec![Rect::new(0, 0, 0, 0); NUM_GLYPHS + 1];
-
-        //surface.set_color_key(true, Color::BLACK)?;
-
-        let mut position = Rect::new(0, 0, font_size.width, font_size.height);
-
-        for i in 32u8..127 {
-            let char_surface = font
-                .render(&(i as char).to_string())
-                .blended(Color::RGBA(255, 255, 255, 0))
-                .map_err(|e: sdl2::ttf::FontError| e.to_string())?;
-
-            println!("{:?}", position);
-            // check to make sure texture fits in the width
-            if position.width() + position.x() as u32 >= FONT_TEXTURE_SIZE {
-                position.set_x(0);
-                position.set_y(position.y() + position.height() as i32 + 1);
-
-                if position.y() as u32 + position.height() >= FONT_TEXTURE_SIZE {
-                    panic!("Ran out of atlas space for glyphs")
+        let chars: Vec<char> = line.chars().collect();
+        let mut shaped = Vec::with_capacity(chars.len());
+        let mut pen = 0;
+        let mut column = 0;
+        while column < chars.len() {
+            let style = style_at(column);
+            let piece = if chars[column] == '\t' {
+                let stop = (self.tab_width as i32 * self.get_glyph(&' ', style).advance).max(1);
+                Shaped {
+                    column,
+                    len: 1,
+                    x: pen,
+                    advance: stop - pen.rem_euclid(stop),
+                    glyph: None,
                 }
-            }
-            char_surface.blit(None, surface.as_mut(), position)?;
-            glyphs[usize::from(i)] = position.clone();
-            position.set_x(position.x() + position.width() as i32);
+            } else {
+                let (len, glyph) = self
+                    .find_cluster(&chars[column..], style, &style_at, column)
+                    .unwrap_or((1, *self.get_glyph(&chars[column], style)));
+                Shaped {
+                    column,
+                    len,
+                    x: pen,
+                    advance: glyph.advance,
+                    glyph: Some(glyph),
+                }
+            };
+            pen += piece.advance;
+            column += piece.len;
+            shaped.push(piece);
         }
+        shaped
+    }
 
-        Ok(Atlas { glyphs, font_size })
+    // pixel offset of every character boundary in the line, so offsets[i] is
+    // where the i-th character starts and the last entry is the line width.
+    // Characters inside a ligature share its advance evenly so the cursor can
+    // still step through them
+    pub fn layout_line<F>(&self, line: &str, style_at: F) -> Vec<i32>
+    where
+        F: Fn(usize) -> Style,
+    {
+        let shaped = self.shape_line(line, style_at);
+        let mut offsets = Vec::with_capacity(line.len() + 1);
+        for piece in &shaped {
+            for i in 0..piece.len {
+                offsets.push(piece.x + piece.advance * i as i32 / piece.len as i32);
+            }
+        }
+        offsets.push(
+            shaped
+                .last()
+                .map(|piece| piece.x + piece.advance)
+                .unwrap_or(0),
+        );
+        offsets
     }
 
-    pub fn get_char(&self, character: &char) -> Rect {
-        return self.glyphs[*character as usize];
+    fn find_cluster<F>(
+        &self,
+        chars: &[char],
+        style: Style,
+        style_at: &F,
+        column: usize,
+    ) -> Option<(usize, Glyph)>
+    where
+        F: Fn(usize) -> Style,
+    {
+        if self.clusters.is_empty() {
+            return None;
+        }
+        (2..=MAX_LIGATURE_LEN.min(chars.len()))
+            .rev()
+            .filter(|len| (1..*len).all(|i| style_at(column + i) == style))
+            .find_map(|len| {
+                let sequence: String = chars[..len].iter().collect();
+                self.clusters
+                    .get(&(sequence, style))
+                    .map(|glyph| (len, *glyph))
+            })
     }
 
-    pub fn get_font_size(&self) -> &Dimensions {
-        &self.font_size
+    fn glyph_index(character: char, style: Style) -> usize {
+        let block = Style::ALL.iter().position(|s| *s == style).unwrap();
+        block * NUM_GLYPHS + character as usize
     }
-    // fn create_glyph_cache(font_path: &str) -> Vec<Rect> {
-    //     Vec::new()
-    // }
 }
@@ -0,0 +1,244 @@
+use std::{
+    fmt::Display,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const USAGE: &str = "Usage: text_editor [OPTIONS] [+LINE[:COL]] <FILE[:LINE[:COL]]>...
+
+Options:
+  --font <PATH>        TrueType font to render with
+  --font-size <SIZE>   font size in points
+  --width <PIXELS>     initial window width
+  --height <PIXELS>    initial window height
+  --readonly           open files without allowing edits
+  --config <PATH>      read settings from this file instead of the default
+  --frame-times        print the average time taken to draw a frame to stderr
+  --version            print the version and exit
+  --help               print this message and exit";
+
+// the same font sizes the config file allows
+const FONT_SIZES: RangeInclusive<u16> = 6..=96;
+const WINDOW_SIZES: RangeInclusive<u32> = 100..=16384;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FileArg {
+    pub path: PathBuf,
+    // 1 based line and column to put the cursor on once the file is open
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Args {
+    pub files: Vec<FileArg>,
+    pub font: Option<PathBuf>,
+    pub font_size: Option<u16>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub readonly: bool,
+    pub config: Option<PathBuf>,
+    pub frame_times: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Args),
+    Help,
+    Version,
+}
+
+// parses everything after the program name
+pub fn parse<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut parsed = Args {
+        files: Vec::new(),
+        font: None,
+        font_size: None,
+        width: None,
+        height: None,
+        readonly: false,
+        config: None,
+        frame_times: false,
+    };
+    // a +line[:col] argument applies to the file after it
+    let mut jump: Option<(u32, Option<u32>)> = None;
+    let mut only_files = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if only_files || !arg.starts_with(['-', '+']) || arg == "-" {
+            let mut file = parse_file(&arg)?;
+            if let Some((line, column)) = jump.take() {
+                file.line = Some(line);
+                file.column = column;
+            }
+            parsed.files.push(file);
+            continue;
+        }
+        if let Some(position) = arg.strip_prefix('+') {
+            jump = Some(parse_position(position).ok_or(format!("invalid position '{arg}'"))?);
+            continue;
+        }
+        match arg.as_str() {
+            "--" => only_files = true,
+            "--help" | "-h" => return Ok(Command::Help),
+            "--version" | "-V" => return Ok(Command::Version),
+            "--readonly" => parsed.readonly = true,
+            "--frame-times" => parsed.frame_times = true,
+            "--font" => parsed.font = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--config" => parsed.config = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--font-size" => parsed.font_size = Some(number(&arg, args.next(), FONT_SIZES)?),
+            "--width" => parsed.width = Some(number(&arg, args.next(), WINDOW_SIZES)?),
+            "--height" => parsed.height = Some(number(&arg, args.next(), WINDOW_SIZES)?),
+            _ => return Err(format!("unknown option '{arg}'")),
+        }
+    }
+
+    if jump.is_some() {
+        return Err("a +line position must be followed by a file".to_string());
+    }
+    if parsed.files.is_empty() {
+        return Err("no file given".to_string());
+    }
+    Ok(Command::Run(parsed))
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or(format!("'{option}' needs a value"))
+}
+
+fn number<T>(option: &str, value: Option<String>, range: RangeInclusive<T>) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + Display,
+{
+    let value = self::value(option, value)?;
+    match value.parse::<T>() {
+        Ok(number) if range.contains(&number) => Ok(number),
+        _ => Err(format!(
+            "'{option}' must be a number from {} to {}, not '{value}'",
+            range.start(),
+            range.end()
+        )),
+    }
+}
+
+// line[:col], both 1 based
+fn parse_position(position: &str) -> Option<(u32, Option<u32>)> {
+    let mut parts = position.splitn(2, ':');
+    let line = parts.next()?.parse::<u32>().ok().filter(|line| *line > 0)?;
+    let column = match parts.next() {
+        Some(column) => Some(column.parse::<u32>().ok().filter(|column| *column > 0)?),
+        None => None,
+    };
+    Some((line, column))
+}
+
+// a file can carry its own position as file:line[:col], unless a file with
+// the whole name exists
+fn parse_file(arg: &str) -> Result<FileArg, String> {
+    let mut file = FileArg {
+        path: PathBuf::from(arg),
+        line: None,
+        column: None,
+    };
+    if Path::new(arg).exists() {
+        return Ok(file);
+    }
+    for (index, _) in arg.match_indices(':') {
+        if index == 0 {
+            continue;
+        }
+        if let Some((line, column)) = parse_position(&arg[index + 1..]) {
+            file.path = PathBuf::from(&arg[..index]);
+            file.line = Some(line);
+            file.column = column;
+            break;
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Args, Command, FileArg};
+    use std::path::PathBuf;
+
+    fn args(args: &[&str]) -> Result<Command, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn file(path: &str, line: Option<u32>, column: Option<u32>) -> FileArg {
+        FileArg {
+            path: PathBuf::from(path),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn files_and_positions() {
+        let Ok(Command::Run(parsed)) = args(&["a.rs", "+12", "b.rs", "+3:4", "c.rs", "d.rs:5:6"])
+        else {
+            panic!("expected the arguments to parse");
+        };
+        assert_eq!(
+            parsed.files,
+            vec![
+                file("a.rs", None, None),
+                file("b.rs", Some(12), None),
+                file("c.rs", Some(3), Some(4)),
+                file("d.rs", Some(5), Some(6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            args(&[
+                "--font",
+                "mono.ttf",
+                "--font-size",
+                "20",
+                "--width",
+                "1024",
+                "--height",
+                "768",
+                "--readonly",
+                "--config",
+                "editor.toml",
+                "--frame-times",
+                "--",
+                "--weird-name"
+            ]),
+            Ok(Command::Run(Args {
+                files: vec![file("--weird-name", None, None)],
+                font: Some(PathBuf::from("mono.ttf")),
+                font_size: Some(20),
+                width: Some(1024),
+                height: Some(768),
+                readonly: true,
+                config: Some(PathBuf::from("editor.toml")),
+                frame_times: true,
+            }))
+        );
+        assert_eq!(args(&["--help", "a.rs"]), Ok(Command::Help));
+        assert_eq!(args(&["--version"]), Ok(Command::Version));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(args(&[]).is_err());
+        assert!(args(&["--font-size", "big", "a.rs"]).is_err());
+        assert!(args(&["--font-size", "500", "a.rs"]).is_err());
+        assert!(args(&["--width", "0", "a.rs"]).is_err());
+        assert!(args(&["--font"]).is_err());
+        assert!(args(&["--bogus", "a.rs"]).is_err());
+        assert!(args(&["a.rs", "+0"]).is_err());
+        assert!(args(&["a.rs", "+2"]).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+// every action the editor can run by name, from a key binding or the palette
+pub struct Command {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const COMMANDS: [Command; 57] = [
+    Command {
+        name: "save",
+        description: "Write the buffer to its file",
+    },
+    Command {
+        name: "undo",
+        description: "Undo the last edit",
+    },
+    Command {
+        name: "redo",
+        description: "Redo the last undone edit",
+    },
+    Command {
+        name: "undo_older",
+        description: "Go back to the text as it was before, on whichever branch",
+    },
+    Command {
+        name: "undo_newer",
+        description: "Go forward to the text as it was after, on whichever branch",
+    },
+    Command {
+        name: "undo_tree",
+        description: "Show or hide the undo history and its branches",
+    },
+    Command {
+        name: "delete_backward",
+        description: "Delete the character before the cursor",
+    },
+    Command {
+        name: "indent",
+        description: "Insert a tab's worth of spaces",
+    },
+    Command {
+        name: "newline",
+        description: "Break the line at the cursor",
+    },
+    Command {
+        name: "cursor_left",
+        description: "Move the cursor left",
+    },
+    Command {
+        name: "cursor_right",
+        description: "Move the cursor right",
+    },
+    Command {
+        name: "cursor_up",
+        description: "Move the cursor up a line",
+    },
+    Command {
+        name: "cursor_down",
+        description: "Move the cursor down a line",
+    },
+    Command {
+        name: "line_start",
+        description: "Move the cursor to the start of the line",
+    },
+    Command {
+        name: "line_end",
+        description: "Move the cursor to the end of the line",
+    },
+    Command {
+        name: "smart_home",
+        description: "Move the cursor to the first non-blank character, then the line start",
+    },
+    Command {
+        name: "word_left",
+        description: "Move the cursor to the start of the previous word",
+    },
+    Command {
+        name: "word_right",
+        description: "Move the cursor to the end of the next word",
+    },
+    Command {
+        name: "paragraph_up",
+        description: "Move the cursor to the blank line before the paragraph",
+    },
+    Command {
+        name: "paragraph_down",
+        description: "Move the cursor to the blank line after the paragraph",
+    },
+    Command {
+        name: "delete_word_backward",
+        description: "Delete back to the start of the previous word",
+    },
+    Command {
+        name: "delete_word_forward",
+        description: "Delete up to the end of the next word",
+    },
+    Command {
+        name: "kill_line",
+        description: "Cut to the end of the line into the kill ring",
+    },
+    Command {
+        name: "kill_region",
+        description: "Cut the region or the selections into the kill ring",
+    },
+    Command {
+        name: "copy_region",
+        description: "Copy the region or the selections into the kill ring",
+    },
+    Command {
+        name: "yank",
+        description: "Paste the last thing killed",
+    },
+    Command {
+        name: "yank_pop",
+        description: "Replace what was just yanked with the kill before it",
+    },
+    Command {
+        name: "set_mark",
+        description: "Start a region at the cursor",
+    },
+    Command {
+        name: "cancel",
+        description: "Drop the mark",
+    },
+    Command {
+        name: "add_next_occurrence",
+        description: "Select the word at each cursor, then add a cursor on the next match",
+    },
+    Command {
+        name: "add_cursor_above",
+        description: "Add a cursor on the line above, or grow the column selection up",
+    },
+    Command {
+        name: "add_cursor_below",
+        description: "Add a cursor on the line below, or grow the column selection down",
+    },
+    Command {
+        name: "block_left",
+        description: "Shrink or grow the column selection a column left",
+    },
+    Command {
+        name: "block_right",
+        description: "Shrink or grow the column selection a column right",
+    },
+    Command {
+        name: "single_cursor",
+        description: "Drop every cursor but the primary one",
+    },
+    Command {
+        name: "next_buffer",
+        description: "Switch to the next tab",
+    },
+    Command {
+        name: "previous_buffer",
+        description: "Switch to the previous tab",
+    },
+    Command {
+        name: "open_file",
+        description: "Open a file in a new tab",
+    },
+    Command {
+        name: "find_file",
+        description: "Search the working directory for a file to open",
+    },
+    Command {
+        name: "toggle_file_tree",
+        description: "Show or hide the file tree",
+    },
+    Command {
+        name: "focus_file_tree",
+        description: "Move between the file tree and the text",
+    },
+    Command {
+        name: "new_file",
+        description: "Create a file or directory next to the one selected in the file tree",
+    },
+    Command {
+        name: "rename_file",
+        description: "Rename or move the file selected in the file tree",
+    },
+    Command {
+        name: "delete_file",
+        description: "Delete the file selected in the file tree, asking first",
+    },
+    Command {
+        name: "close_buffer",
+        description: "Close the tab, asking first when it has unsaved changes",
+    },
+    Command {
+        name: "split_right",
+        description: "Split the pane with a new one on the right",
+    },
+    Command {
+        name: "split_down",
+        description: "Split the pane with a new one below",
+    },
+    Command {
+        name: "close_pane",
+        description: "Close the pane, leaving its buffer open",
+    },
+    Command {
+        name: "focus_pane_left",
+        description: "Move to the pane on the left",
+    },
+    Command {
+        name: "focus_pane_right",
+        description: "Move to the pane on the right",
+    },
+    Command {
+        name: "focus_pane_up",
+        description: "Move to the pane above",
+    },
+    Command {
+        name: "focus_pane_down",
+        description: "Move to the pane below",
+    },
+    Command {
+        name: "focus_next_pane",
+        description: "Move to the next pane",
+    },
+    Command {
+        name: "toggle_wrap",
+        description: "Wrap long lines onto the rows below or let them run off the edge",
+    },
+    Command {
+        name: "goto_line",
+        description: "Jump to a line number",
+    },
+    Command {
+        name: "command_palette",
+        description: "Search for a command to run",
+    },
+    Command {
+        name: "quit",
+        description: "Close the editor",
+    },
+];
+
+pub fn names() -> Vec<&'static str> {
+    COMMANDS.iter().map(|command| command.name).collect()
+}
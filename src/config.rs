@@ -0,0 +1,376 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::Deserialize;
+use toml::{Spanned, Value};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+// a set of key bindings and behaviour to start from
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum Preset {
+    #[default]
+    Default,
+    // modal editing on top of the default bindings
+    Vim,
+    Emacs,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    pub tab_width: usize,
+    pub keymap: Preset,
+    // characters besides letters and digits that word motions treat as part
+    // of a word
+    pub word_chars: String,
+    pub font: Option<PathBuf>,
+    pub font_size: u16,
+    pub ligatures: bool,
+    pub cursor_blink_on: Duration,
+    pub cursor_blink_off: Duration,
+    pub background: Colour,
+    pub foreground: Colour,
+    pub cursor: Colour,
+    // chord or sequence to command name, along with the line it came from so
+    // the keymap can point at it when the binding doesn't make sense
+    pub keybindings: Vec<(String, String, usize)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            tab_width: 4,
+            keymap: Preset::Default,
+            word_chars: "_".to_string(),
+            font: None,
+            font_size: 16,
+            ligatures: true,
+            cursor_blink_on: Duration::from_millis(400),
+            cursor_blink_off: Duration::from_millis(600),
+            background: Colour { r: 0, g: 0, b: 0 },
+            foreground: Colour {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            cursor: Colour {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            keybindings: Vec::new(),
+        }
+    }
+}
+
+// the settings in a table, each with where its value is in the file
+type Table = BTreeMap<String, Spanned<Value>>;
+
+// the file as TOML reads it, the values are checked as they're applied so
+// errors can point at the line they're on
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    tab_width: Option<Spanned<Value>>,
+    keymap: Option<Spanned<Value>>,
+    word_chars: Option<Spanned<Value>>,
+    font: Table,
+    cursor: Table,
+    colours: Table,
+    keybindings: Table,
+}
+
+impl Config {
+    // $XDG_CONFIG_HOME/text_editor/config.toml, falling back to ~/.config
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("text_editor").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}:{}", path.display(), e))
+    }
+
+    // errors are prefixed with the line number they happened on
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let line_of = |offset: usize| text[..offset.min(text.len())].matches('\n').count() + 1;
+        let file: File = toml::from_str(text).map_err(|e| {
+            let line = e.span().map_or(1, |span| line_of(span.start));
+            // the message can run onto more lines about where it went wrong
+            format!("{line}: {}", e.message().replace('\n', ", "))
+        })?;
+
+        let top_level = [
+            ("tab_width", file.tab_width),
+            ("keymap", file.keymap),
+            ("word_chars", file.word_chars),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some(("", key.to_string(), value?)));
+        let tables = [
+            ("font", file.font),
+            ("cursor", file.cursor),
+            ("colours", file.colours),
+            ("keybindings", file.keybindings),
+        ]
+        .into_iter()
+        .flat_map(|(table, values)| {
+            values
+                .into_iter()
+                .map(move |(key, value)| (table, key, value))
+        });
+        // in the order they're written, so bindings keep their order
+        let mut settings: Vec<(&str, String, Spanned<Value>)> = top_level.chain(tables).collect();
+        settings.sort_by_key(|(_, _, value)| value.span().start);
+
+        let mut config = Config::default();
+        for (table, key, value) in settings {
+            let line_num = line_of(value.span().start);
+            config
+                .set(table, &key, value.into_inner(), line_num)
+                .map_err(|e| format!("{line_num}: {e}"))?;
+        }
+        Ok(config)
+    }
+
+    fn set(&mut self, table: &str, key: &str, value: Value, line_num: usize) -> Result<(), String> {
+        match (table, key) {
+            ("", "tab_width") => self.tab_width = integer(&value, key, 1, 16)? as usize,
+            ("", "keymap") => {
+                self.keymap = match string(&value, key)? {
+                    "default" => Preset::Default,
+                    "vim" => Preset::Vim,
+                    "emacs" => Preset::Emacs,
+                    other => {
+                        return Err(format!(
+                            "unknown keymap '{other}', expected default, vim or emacs"
+                        ))
+                    }
+                }
+            }
+            ("", "word_chars") => self.word_chars = string(&value, key)?.to_string(),
+            ("font", "path") => self.font = Some(PathBuf::from(string(&value, key)?)),
+            // the glyph atlas grows to hold any of these
+            ("font", "size") => self.font_size = integer(&value, key, 6, 96)? as u16,
+            ("font", "ligatures") => self.ligatures = boolean(&value, key)?,
+            ("cursor", "blink_on_ms") => {
+                self.cursor_blink_on = Duration::from_millis(integer(&value, key, 50, 10_000)?)
+            }
+            ("cursor", "blink_off_ms") => {
+                self.cursor_blink_off = Duration::from_millis(integer(&value, key, 50, 10_000)?)
+            }
+            ("colours", "background") => self.background = colour(&value, key)?,
+            ("colours", "foreground") => self.foreground = colour(&value, key)?,
+            ("colours", "cursor") => self.cursor = colour(&value, key)?,
+            ("keybindings", _) => {
+                let command = string(&value, key)?;
+                self.keybindings
+                    .push((key.to_string(), command.to_string(), line_num));
+            }
+            _ => return Err(format!("unknown setting '{key}' in [{table}]")),
+        }
+        Ok(())
+    }
+}
+
+// remembers when the config file was last changed so it can be reloaded
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf) -> ConfigWatcher {
+        let modified = Self::modified(&path);
+        ConfigWatcher { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // true once each time the file's modification time moves
+    pub fn changed(&mut self) -> bool {
+        let modified = Self::modified(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            return modified.is_some();
+        }
+        false
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|data| data.modified()).ok()
+    }
+}
+
+fn string<'a>(value: &'a Value, key: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(format!("'{key}' must be a string")),
+    }
+}
+
+fn boolean(value: &Value, key: &str) -> Result<bool, String> {
+    match value {
+        Value::Boolean(boolean) => Ok(*boolean),
+        _ => Err(format!("'{key}' must be true or false")),
+    }
+}
+
+fn integer(value: &Value, key: &str, min: u64, max: u64) -> Result<u64, String> {
+    match value {
+        Value::Integer(integer) if (min as i64..=max as i64).contains(integer) => {
+            Ok(*integer as u64)
+        }
+        _ => Err(format!("'{key}' must be a number from {min} to {max}")),
+    }
+}
+
+// "#rrggbb"
+fn colour(value: &Value, key: &str) -> Result<Colour, String> {
+    let error = || format!("'{key}' must be a colour like \"#1e1e2e\"");
+    let hex = string(value, key)?.strip_prefix('#').ok_or_else(error)?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(error());
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).unwrap();
+    Ok(Colour {
+        r: channel(0),
+        g: channel(2),
+        b: channel(4),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Colour, Config, Preset};
+    use std::{path::PathBuf, time::Duration};
+
+    #[test]
+    fn defaults() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(
+            Config::parse("# nothing here\n\n").unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn settings() {
+        let config = Config::parse(
+            r##"
+tab_width = 2 # spaces
+keymap = "vim"
+word_chars = "_-"
+
+[font]
+path = "/fonts/Mono #1.ttf"
+size = 18
+ligatures = false
+
+[cursor]
+blink_on_ms = 500
+blink_off_ms = 1_000
+
+[colours]
+background = "#1e1e2e"
+
+[keybindings]
+"ctrl+s" = "save"
+"ctrl+=" = "zoom_in"
+"##,
+        )
+        .unwrap();
+        assert_eq!(config.tab_width, 2);
+        assert_eq!(config.keymap, Preset::Vim);
+        assert_eq!(config.word_chars, "_-");
+        assert_eq!(config.font, Some(PathBuf::from("/fonts/Mono #1.ttf")));
+        assert_eq!(config.font_size, 18);
+        assert!(!config.ligatures);
+        assert_eq!(config.cursor_blink_on, Duration::from_millis(500));
+        assert_eq!(config.cursor_blink_off, Duration::from_millis(1000));
+        assert_eq!(
+            config.background,
+            Colour {
+                r: 0x1e,
+                g: 0x1e,
+                b: 0x2e
+            }
+        );
+        assert_eq!(config.foreground, Config::default().foreground);
+        assert_eq!(
+            config.keybindings,
+            vec![
+                ("ctrl+s".to_string(), "save".to_string(), 19),
+                ("ctrl+=".to_string(), "zoom_in".to_string(), 20)
+            ]
+        );
+    }
+
+    #[test]
+    fn any_toml() {
+        let config = Config::parse(
+            r#"
+word_chars = '_\'
+font.size = 20
+cursor = { blink_on_ms = 300 }
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.word_chars, "_\\");
+        assert_eq!(config.font_size, 20);
+        assert_eq!(config.cursor_blink_on, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        assert_eq!(
+            Config::parse("keymap = \"vim\"\ntab_width = 0"),
+            Err("2: 'tab_width' must be a number from 1 to 16".to_string())
+        );
+        assert_eq!(
+            Config::parse("\n[colours]\nforeground = \"white\""),
+            Err("3: 'foreground' must be a colour like \"#1e1e2e\"".to_string())
+        );
+        assert_eq!(
+            Config::parse("[font]\nsize = \"big\""),
+            Err("2: 'size' must be a number from 6 to 96".to_string())
+        );
+        assert_eq!(
+            Config::parse("[colours]\nbackground = [0, 0, 0]"),
+            Err("2: 'background' must be a string".to_string())
+        );
+        assert_eq!(
+            Config::parse("[font]\nweight = 400"),
+            Err("2: unknown setting 'weight' in [font]".to_string())
+        );
+        // anything TOML itself rejects
+        let unknown = |line: usize, key: &str| {
+            format!(
+                "{line}: unknown field `{key}`, expected one of `tab_width`, `keymap`, \
+                 `word_chars`, `font`, `cursor`, `colours`, `keybindings`"
+            )
+        };
+        assert_eq!(Config::parse("[fonts]"), Err(unknown(1, "fonts")));
+        assert_eq!(Config::parse("\ntabwidth = 4"), Err(unknown(2, "tabwidth")));
+        assert_eq!(
+            Config::parse("\n[font]\npath = \"unterminated"),
+            Err("3: invalid basic string".to_string())
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// positions are (line, column)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cursor {
+    pub position: (u32, u32),
+    // the other end of the selection when there is one
+    pub anchor: Option<(u32, u32)>,
+    // the column moving up and down tries to get back to
+    pub goal_column: u32,
+}
+
+impl Cursor {
+    pub fn new(position: (u32, u32)) -> Cursor {
+        Cursor {
+            position,
+            anchor: None,
+            goal_column: position.1,
+        }
+    }
+
+    pub fn with_anchor(position: (u32, u32), anchor: (u32, u32)) -> Cursor {
+        Cursor {
+            anchor: Some(anchor),
+            ..Cursor::new(position)
+        }
+    }
+
+    // the selected range in order, None when nothing is selected
+    pub fn selection(&self) -> Option<((u32, u32), (u32, u32))> {
+        let anchor = self.anchor.filter(|anchor| *anchor != self.position)?;
+        Some((anchor.min(self.position), anchor.max(self.position)))
+    }
+
+    fn start(&self) -> (u32, u32) {
+        self.anchor
+            .map_or(self.position, |anchor| anchor.min(self.position))
+    }
+
+    fn end(&self) -> (u32, u32) {
+        self.anchor
+            .map_or(self.position, |anchor| anchor.max(self.position))
+    }
+}
+
+// kept sorted with cursors that touch merged into one. The primary cursor is
+// the one placed last, the view follows it and single cursor commands use it
+#[derive(Clone)]
+pub struct Cursors {
+    cursors: Vec<Cursor>,
+    primary: usize,
+}
+
+impl Cursors {
+    pub fn new(cursor: Cursor) -> Cursors {
+        Cursors {
+            cursors: vec![cursor],
+            primary: 0,
+        }
+    }
+
+    pub fn primary(&self) -> &Cursor {
+        &self.cursors[self.primary]
+    }
+
+    pub fn all(&self) -> &[Cursor] {
+        &self.cursors
+    }
+
+    // drops every other cursor
+    pub fn set(&mut self, cursor: Cursor) {
+        self.cursors = vec![cursor];
+        self.primary = 0;
+    }
+
+    // replaces every cursor, primary is the index of the new primary one
+    pub fn set_all(&mut self, cursors: Vec<Cursor>, primary: usize) {
+        debug_assert!(primary < cursors.len());
+        self.cursors = cursors;
+        self.primary = primary;
+        self.normalise();
+    }
+
+    pub fn add(&mut self, cursor: Cursor) {
+        self.cursors.push(cursor);
+        self.primary = self.cursors.len() - 1;
+        self.normalise();
+    }
+
+    // swaps in moved or edited cursors, one for each existing cursor in the
+    // same order so the primary one stays primary
+    pub fn replace(&mut self, cursors: Vec<Cursor>) {
+        debug_assert_eq!(cursors.len(), self.cursors.len());
+        self.cursors = cursors;
+        self.normalise();
+    }
+
+    fn normalise(&mut self) {
+        let mut tagged: Vec<(Cursor, bool)> = self
+            .cursors
+            .iter()
+            .enumerate()
+            .map(|(index, cursor)| (*cursor, index == self.primary))
+            .collect();
+        tagged.sort_by_key(|(cursor, _)| (cursor.start(), cursor.end()));
+
+        let mut merged: Vec<(Cursor, bool)> = Vec::with_capacity(tagged.len());
+        for (cursor, primary) in tagged {
+            match merged.last_mut() {
+                Some((last, last_primary))
+                    if cursor.start() < last.end() || cursor.start() == last.start() =>
+                {
+                    *last = Self::merge(*last, cursor);
+                    *last_primary |= primary;
+                }
+                _ => merged.push((cursor, primary)),
+            }
+        }
+        self.primary = merged.iter().position(|(_, primary)| *primary).unwrap_or(0);
+        self.cursors = merged.into_iter().map(|(cursor, _)| cursor).collect();
+    }
+
+    // covers both, keeping the direction of the first
+    fn merge(first: Cursor, second: Cursor) -> Cursor {
+        let start = first.start().min(second.start());
+        let end = first.end().max(second.end());
+        if start == end {
+            return first;
+        }
+        match first.anchor.is_some_and(|anchor| anchor > first.position) {
+            true => Cursor::with_anchor(start, end),
+            false => Cursor::with_anchor(end, start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cursor, Cursors};
+
+    #[test]
+    fn sorted_and_merged() {
+        let mut cursors = Cursors::new(Cursor::new((2, 0)));
+        cursors.add(Cursor::new((0, 4)));
+        cursors.add(Cursor::new((1, 1)));
+        cursors.add(Cursor::new((0, 4)));
+        let positions: Vec<(u32, u32)> = cursors.all().iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![(0, 4), (1, 1), (2, 0)]);
+        assert_eq!(cursors.primary().position, (0, 4));
+
+        cursors.add(Cursor::with_anchor((1, 3), (0, 2)));
+        let positions: Vec<(u32, u32)> = cursors.all().iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![(1, 3), (2, 0)]);
+        assert_eq!(cursors.primary().selection(), Some(((0, 2), (1, 3))));
+
+        cursors.replace(vec![Cursor::new((2, 0)), Cursor::new((2, 0))]);
+        assert_eq!(cursors.all().len(), 1);
+        cursors.set(Cursor::new((5, 5)));
+        assert_eq!(cursors.all(), &[Cursor::new((5, 5))]);
+
+        cursors.set_all(vec![Cursor::new((3, 1)), Cursor::new((1, 1))], 0);
+        assert_eq!(cursors.primary().position, (3, 1));
+        assert_eq!(cursors.all()[0].position, (1, 1));
+    }
+}
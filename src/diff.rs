@@ -0,0 +1,144 @@
+// how many unchanged lines show around each change
+const CONTEXT: usize = 3;
+// past this many line pairs to compare the middle of the texts is shown as
+// all removed then all added rather than searched for common lines
+const MAX_COMPARISONS: usize = 4_000_000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Op {
+    Same,
+    Delete,
+    Insert,
+}
+
+// the lines that differ between two texts as a unified diff, empty when
+// they're the same
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = line_ops(&old_lines, &new_lines);
+    // where each op is in both texts
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for op in &ops {
+        positions.push((old_line, new_line));
+        match op {
+            Op::Same => (old_line, new_line) = (old_line + 1, new_line + 1),
+            Op::Delete => old_line += 1,
+            Op::Insert => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|i| ops[*i] != Op::Same).collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+    let mut diff = format!("--- {old_name}\n+++ {new_name}\n");
+    let mut index = 0;
+    while index < changes.len() {
+        // changes close enough for their context to touch share a hunk
+        let mut last = index;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+        let start = changes[index].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(ops.len());
+        let (old_start, new_start) = positions[start];
+        let (old_len, new_len) = (positions[end].0 - old_start, positions[end].1 - new_start);
+        // an empty side counts from the line before it, like diff -u
+        let from = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            from(old_start, old_len),
+            old_len,
+            from(new_start, new_len),
+            new_len
+        ));
+        for op_index in start..end {
+            let (old_line, new_line) = positions[op_index];
+            let line = match ops[op_index] {
+                Op::Same => format!(" {}", old_lines[old_line]),
+                Op::Delete => format!("-{}", old_lines[old_line]),
+                Op::Insert => format!("+{}", new_lines[new_line]),
+            };
+            diff.push_str(&line);
+            diff.push('\n');
+        }
+        index = last + 1;
+    }
+    diff
+}
+
+// the shortest way to turn one list of lines into the other, found with
+// the longest common subsequence of the part between a shared start and end
+fn line_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![Op::Same; prefix];
+    if old_middle.len() * new_middle.len() > MAX_COMPARISONS {
+        ops.extend(std::iter::repeat_n(Op::Delete, old_middle.len()));
+        ops.extend(std::iter::repeat_n(Op::Insert, new_middle.len()));
+    } else {
+        // common[i][j] is how many lines old_middle[i..] and new_middle[j..]
+        // have in common
+        let width = new_middle.len() + 1;
+        let mut common = vec![0u32; (old_middle.len() + 1) * width];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                common[i * width + j] = match old_middle[i] == new_middle[j] {
+                    true => common[(i + 1) * width + j + 1] + 1,
+                    false => common[(i + 1) * width + j].max(common[i * width + j + 1]),
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() || j < new_middle.len() {
+            if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+                ops.push(Op::Same);
+                (i, j) = (i + 1, j + 1);
+            } else if j == new_middle.len()
+                || (i < old_middle.len()
+                    && common[(i + 1) * width + j] >= common[i * width + j + 1])
+            {
+                ops.push(Op::Delete);
+                i += 1;
+            } else {
+                ops.push(Op::Insert);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Same, suffix));
+    ops
+}
+
+#[cfg(test)]
+mod test {
+    use super::unified;
+
+    #[test]
+    fn hunks() {
+        assert_eq!(unified("same\n", "same\n", "a", "b"), "");
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n15\nsixteen\n";
+        assert_eq!(
+            unified(old, new, "disk", "buffer"),
+            "--- disk\n+++ buffer\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -11,5 +11,5 @@\n 11\n 12\n 13\n-14\n 15\n+sixteen\n"
+        );
+        assert_eq!(
+            unified("", "new\n", "a", "b"),
+            "--- a\n+++ b\n@@ -0,0 +1,1 @@\n+new\n"
+        );
+    }
+}
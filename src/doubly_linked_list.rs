@@ -106,6 +106,61 @@ impl<T> List<T> {
         }
     }
 
+    // inserts so the element ends up at position index, appending if the
+    // index is past the end of the list
+    pub fn insert(&mut self, index: usize, element: T) {
+        unsafe {
+            let next = self.node_at(index);
+            if next.is_null() {
+                return self.push_back(element);
+            }
+            if next == self.head {
+                return self.push_front(element);
+            }
+            let new_node = Box::into_raw(Box::new(Node {
+                elem: element,
+                next,
+                prev: (*next).prev,
+            }));
+            (*(*next).prev).next = new_node;
+            (*next).prev = new_node;
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        unsafe {
+            let node = self.node_at(index);
+            if node.is_null() {
+                return None;
+            }
+            if node == self.head {
+                return self.pop_front();
+            }
+            if node == self.tail {
+                return self.pop_back();
+            }
+            (*(*node).prev).next = (*node).next;
+            (*(*node).next).prev = (*node).prev;
+            let removed_node = Box::from_raw(node);
+            Some(removed_node.elem)
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        unsafe { self.node_at(index).as_mut().map(|node| &mut node.elem) }
+    }
+
+    fn node_at(&self, index: usize) -> *mut Node<T> {
+        let mut node = self.head;
+        for _ in 0..index {
+            if node.is_null() {
+                break;
+            }
+            node = unsafe { (*node).next };
+        }
+        node
+    }
+
     pub fn into_iter(self) -> IntoIter<T> {
         IntoIter(self)
     }
@@ -113,8 +168,8 @@ impl<T> List<T> {
     pub fn iter(&self) -> Iter<T> {
         unsafe {
             Iter {
-                next: Some(&*self.head),
-                next_back: Some(&*self.tail),
+                next: self.head.as_ref(),
+                next_back: self.tail.as_ref(),
             }
         }
     }
@@ -122,8 +177,8 @@ impl<T> List<T> {
     pub fn iter_mut(&mut self) -> IterMut<T> {
         unsafe {
             IterMut {
-                next: Some(&mut *self.head),
-                next_back: Some(&mut *self.tail),
+                next: self.head.as_mut(),
+                next_back: self.tail.as_mut(),
             }
         }
     }
@@ -310,6 +365,31 @@ mod test {
         assert_eq!(list.peek_front().cloned(), Some(3));
     }
 
+    #[test]
+    fn insert_remove() {
+        let mut list = List::new();
+        list.insert(0, 2);
+        list.insert(0, 1);
+        list.insert(5, 4);
+        list.insert(2, 3);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), Some(&4));
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(list.remove(2), Some(3));
+        assert_eq!(list.remove(0), Some(1));
+        assert_eq!(list.remove(1), Some(4));
+        assert_eq!(list.remove(1), None);
+        assert_eq!(list.get_mut(0), Some(&mut 2));
+        assert_eq!(list.remove(0), Some(2));
+        assert_eq!(list.peek_front(), None);
+        assert_eq!(list.peek_back(), None);
+    }
+
     #[test]
     fn into_iter() {
         let mut list = List::new();
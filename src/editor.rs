@@ -1,20 +1,34 @@
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
 use sdl2::{
-    event::Event,
-    keyboard::Keycode,
-    pixels::{self, Color, PixelFormatEnum},
-    surface::Surface,
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
     Sdl,
 };
 
 use crate::{
-    atlas::Atlas,
-    screen::Screen,
+    atlas::{Atlas, Style, DEFAULT_FONT},
+    cli::Args,
+    commands::{self, COMMANDS},
+    config::{Config, ConfigWatcher, Preset},
+    cursors::Cursor,
+    diff,
+    file_finder::FileWalk,
+    file_tree::FileTree,
+    file_watcher::FileWatcher,
+    history,
+    keymap::{Chord, KeyPress, Keymap, Modifiers},
+    kill_ring::KillRing,
+    layout::Side,
+    markdown, motion,
+    palette::{Item, Palette},
+    screen::{Screen, StyledRun, View},
     text_buffer::{self, Buffer},
+    vim::{self, Vim},
 };
 
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
@@ -23,36 +37,346 @@ pub struct Dimensions {
     pub width: u32,
 }
 
-pub struct Editor<'a> {
+pub struct Editor {
     atlas: Atlas,
     screen: Screen,
     sdl_context: Sdl,
-    surface: Surface<'a>,
+    // the buffer on screen
     text_buffer: Buffer,
+    // every other open buffer in tab order, the one on screen would sit at
+    // index active
+    tabs: Vec<Tab>,
+    active: usize,
+    config: Config,
+    config_watcher: Option<ConfigWatcher>,
+    // None when inotify couldn't be set up, changes on disk go unnoticed then
+    file_watcher: Option<FileWatcher>,
+    // files written by something else that haven't been reloaded or asked
+    // about yet
+    written: Vec<PathBuf>,
+    // a message in the status line until the next key press
+    status: Option<String>,
+    keymap: Keymap,
+    // the overlay taking keyboard input, if one is open, and what its answer is for
+    palette: Option<(PaletteKind, Palette)>,
+    // lists files for the file finder while it's open
+    file_walk: Option<FileWalk>,
+    // the sidebar listing the working directory, when it's open, and whether
+    // keys go to it rather than the text
+    file_tree: Option<FileTree>,
+    tree_focused: bool,
+    // the undo history of the buffer on screen is drawn over the text, it
+    // stays up while moving through the history
+    show_undo_tree: bool,
+    // set when the vim keymap is chosen, keys go through it before the keymap
+    vim: Option<Vim>,
+    kill_ring: KillRing,
+    // one end of the region, the cursor is the other
+    mark: Option<(u32, u32)>,
+    // where the last yank put its text so yank_pop can swap it out
+    last_yank: Option<((u32, u32), (u32, u32))>,
+    // consecutive kills join together and yank_pop only follows a yank
+    last_command: Option<String>,
+    // font settings given on the command line win over the config file
+    font_override: Option<PathBuf>,
+    font_size_override: Option<u16>,
+    readonly: bool,
+    // an alt+drag with the left button is making a column selection
+    dragging_block: bool,
+    frame_times: bool,
 }
 
+// averages how long frames take to draw, only reports when started with
+// --frame-times
+struct FrameTimer {
+    enabled: bool,
+    frames: u32,
+    total: Duration,
+}
+
+const FRAMES_PER_REPORT: u32 = 120;
+const DEFAULT_WIDTH: u32 = 800;
+const DEFAULT_HEIGHT: u32 = 600;
+const SCROLL_LINES: i32 = 3;
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// how often the file finder picks up files its walk has found
+const FILE_WALK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl FrameTimer {
+    fn new(enabled: bool) -> FrameTimer {
+        FrameTimer {
+            enabled,
+            frames: 0,
+            total: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, frame_start: Instant) {
+        if !self.enabled {
+            return;
+        }
+        self.total += frame_start.elapsed();
+        self.frames += 1;
+        if self.frames == FRAMES_PER_REPORT {
+            eprintln!(
+                "average frame time: {:?} over {} frames",
+                self.total / self.frames,
+                self.frames
+            );
+            self.frames = 0;
+            self.total = Duration::ZERO;
+        }
+    }
+}
+
+// an open buffer that isn't on screen
+struct Tab {
+    buffer: Buffer,
+    view: View,
+}
+
+enum PaletteKind {
+    Commands,
+    GotoLine,
+    OpenFile,
+    // paths in the palette are relative to the directory
+    Files(PathBuf),
+    CloseUnsaved,
+    // file tree changes, paths are relative to the tree's root
+    NewFile,
+    RenameFile(PathBuf),
+    DeleteFile(PathBuf),
+    // the open buffer with unsaved changes whose file something else changed
+    ChangedOnDisk(PathBuf),
+    // the open buffer with a swap file left by a run that didn't finish
+    Recover(PathBuf),
+}
+
+// the answers to closing a buffer with unsaved changes, in palette order
+const CLOSE_CHOICES: [(&str, &str); 3] = [
+    ("save", "Save the changes and close"),
+    ("discard", "Close without saving"),
+    ("cancel", "Keep the buffer open"),
+];
+
+const CHANGED_CHOICES: [(&str, &str); 3] = [
+    (
+        "reload",
+        "Load the file from the disk, dropping the unsaved changes",
+    ),
+    ("keep", "Keep the changes here, saving writes over the file"),
+    (
+        "diff",
+        "Open a tab comparing the file on disk with the changes",
+    ),
+];
+
+const RECOVER_CHOICES: [(&str, &str); 2] = [
+    (
+        "recover",
+        "Bring back the unsaved changes from the swap file",
+    ),
+    (
+        "discard",
+        "Delete the swap file and keep the file as it is on disk",
+    ),
+];
+
+const DELETE_CHOICES: [(&str, &str); 2] =
+    [("delete", "Delete it from the disk"), ("cancel", "Keep it")];
+
 #[derive(PartialEq)]
 enum CursorState {
     On,
     Off,
 }
 
-impl<'a> Editor<'a> {
-    pub fn new<P: AsRef<Path>>(dimensions: Dimensions, file_path: P) -> Result<Editor<'a>, String> {
+impl Editor {
+    pub fn new(args: &Args) -> Result<Editor, String> {
         let sdl_context = sdl2::init()?;
 
-        let masks = PixelFormatEnum::RGB24.into_masks().unwrap();
-        let mut surface = Surface::from_pixelmasks(512, 512, &masks).unwrap();
-        surface.set_color_key(true, Color::BLACK)?;
-        let text_buffer = Buffer::open(file_path).unwrap();
-        let screen = Screen::new(&sdl_context, &dimensions, &text_buffer)?;
-        return Ok(Editor {
+        let dimensions = Dimensions {
+            height: args.height.unwrap_or(DEFAULT_HEIGHT),
+            width: args.width.unwrap_or(DEFAULT_WIDTH),
+        };
+        let config_path = args.config.clone().or_else(Config::default_path);
+        let config = match &config_path {
+            Some(path) if args.config.is_some() || path.exists() => Config::load(path)?,
+            _ => Config::default(),
+        };
+        let keymap = Self::build_keymap(&config, config_path.as_deref())?;
+        let vim =
+            (config.keymap == Preset::Vim).then(|| Vim::new(args.readonly, &config.word_chars));
+
+        let (file, other_files) = args
+            .files
+            .split_first()
+            .ok_or("no file to open".to_string())?;
+        let text_buffer = Self::open_file(&file.path)?;
+        // the rest open in tabs behind the first
+        let tabs = other_files
+            .iter()
+            .map(|file| {
+                let view =
+                    View::with_cursor(file.line.unwrap_or(1) - 1, file.column.unwrap_or(1) - 1);
+                Self::open_file(&file.path).map(|buffer| Tab { buffer, view })
+            })
+            .collect::<Result<Vec<Tab>, String>>()?;
+        let mut screen = Screen::new(&sdl_context, &dimensions, &text_buffer)?;
+        if let Some(line) = file.line {
+            let column = file.column.unwrap_or(1);
+            screen.set_cursor(line - 1, column - 1, &text_buffer);
+        }
+        screen.set_colours(config.background, config.foreground, config.cursor);
+        let atlas = Self::build_atlas(
+            &config,
+            args.font.as_deref(),
+            args.font_size,
+            screen.get_scale(),
+        )?;
+        let mut editor = Editor {
             sdl_context,
-            atlas: Atlas::new(16, &mut surface)?,
+            atlas,
             screen,
-            surface,
             text_buffer,
-        });
+            tabs,
+            active: 0,
+            config,
+            config_watcher: config_path.map(ConfigWatcher::new),
+            file_watcher: FileWatcher::new()
+                .map_err(|e| eprintln!("error: {}", e))
+                .ok(),
+            written: Vec::new(),
+            status: None,
+            keymap,
+            palette: None,
+            file_walk: None,
+            file_tree: None,
+            tree_focused: false,
+            show_undo_tree: false,
+            vim,
+            kill_ring: KillRing::new(),
+            mark: None,
+            last_yank: None,
+            last_command: None,
+            font_override: args.font.clone(),
+            font_size_override: args.font_size,
+            readonly: args.readonly,
+            dragging_block: false,
+            frame_times: args.frame_times,
+        };
+        editor.offer_recovery();
+        Ok(editor)
+    }
+
+    fn build_atlas(
+        config: &Config,
+        font_override: Option<&Path>,
+        font_size_override: Option<u16>,
+        scale: f32,
+    ) -> Result<Atlas, String> {
+        let font_path = font_override
+            .or(config.font.as_deref())
+            .unwrap_or(Path::new(DEFAULT_FONT));
+        let mut atlas = Atlas::new(
+            font_path,
+            font_size_override.unwrap_or(config.font_size),
+            config.ligatures,
+            scale,
+        )?;
+        atlas.set_tab_width(config.tab_width);
+        Ok(atlas)
+    }
+
+    fn build_keymap(config: &Config, config_path: Option<&Path>) -> Result<Keymap, String> {
+        Keymap::with_bindings(config.keymap, &config.keybindings, &commands::names()).map_err(|e| {
+            match config_path {
+                Some(path) => format!("{}:{}", path.display(), e),
+                None => e,
+            }
+        })
+    }
+
+    fn rebuild_atlas(&mut self) -> Result<(), String> {
+        self.atlas = Self::build_atlas(
+            &self.config,
+            self.font_override.as_deref(),
+            self.font_size_override,
+            self.screen.get_scale(),
+        )?;
+        Ok(())
+    }
+
+    // picks up changes to the config file, a broken config is reported and
+    // the current settings are kept
+    fn reload_config(&mut self) {
+        let Some(watcher) = self.config_watcher.as_mut() else {
+            return;
+        };
+        if !watcher.changed() {
+            return;
+        }
+        let config = match Config::load(watcher.path()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return;
+            }
+        };
+        let keymap = match Self::build_keymap(&config, Some(watcher.path())) {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return;
+            }
+        };
+        let font_changed = config.font != self.config.font
+            || config.font_size != self.config.font_size
+            || config.ligatures != self.config.ligatures;
+        // a font that can't be loaded or rendered counts as a broken config
+        let atlas = match font_changed {
+            true => match Self::build_atlas(
+                &config,
+                self.font_override.as_deref(),
+                self.font_size_override,
+                self.screen.get_scale(),
+            ) {
+                Ok(atlas) => Some(atlas),
+                Err(e) => {
+                    eprintln!("error: {}: {}", watcher.path().display(), e);
+                    return;
+                }
+            },
+            false => None,
+        };
+        self.keymap = keymap;
+        if config.keymap != self.config.keymap {
+            self.vim =
+                (config.keymap == Preset::Vim).then(|| Vim::new(self.readonly, &config.word_chars));
+            self.screen.set_selections(Vec::new());
+        } else if let Some(vim) = self.vim.as_mut() {
+            vim.set_word_chars(&config.word_chars);
+        }
+        self.config = config;
+        self.screen.set_colours(
+            self.config.background,
+            self.config.foreground,
+            self.config.cursor,
+        );
+        if let Some(atlas) = atlas {
+            self.atlas = atlas;
+        }
+        self.atlas.set_tab_width(self.config.tab_width);
+    }
+
+    // undo carries on from where it was left last session
+    fn open_file(path: &Path) -> Result<Buffer, String> {
+        let mut buffer =
+            Buffer::open(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        if let Some(dir) = history::default_dir() {
+            buffer.restore_history(dir);
+        }
+        Ok(buffer)
     }
 
     pub fn start(&mut self) -> Result<(), String> {
@@ -60,79 +384,1556 @@ impl<'a> Editor<'a> {
         let mut time_since_cursor_change = Instant::now();
 
         let mut event_pump = self.sdl_context.event_pump()?;
+        let mut frame_timer = FrameTimer::new(self.frame_times);
+        let mut needs_redraw = true;
+        // a key that ran a command shouldn't also type its character
+        let mut skip_text_input = false;
 
         'running: loop {
-            self.screen.colour(pixels::Color {
-                r: 0,
-                g: 0,
-                b: 0,
-                a: 0,
-            });
-            self.screen.clear_screen();
-            Self::manage_cursor(&mut time_since_cursor_change, &mut cursor_state, false);
+            self.flush_journals();
+            self.reload_config();
+            if self.check_files_on_disk() {
+                needs_redraw = true;
+            }
+            if self.poll_file_walk() {
+                needs_redraw = true;
+            }
+            let cursor_was_on = cursor_state == CursorState::On;
+            let blink_deadline = Self::manage_cursor(
+                &mut time_since_cursor_change,
+                &mut cursor_state,
+                false,
+                &self.config,
+            );
+            if needs_redraw || cursor_was_on != (cursor_state == CursorState::On) {
+                let frame_start = Instant::now();
+                self.draw(&cursor_state);
+                frame_timer.record(frame_start);
+                needs_redraw = false;
+            }
+
+            // sleep until something happens or the cursor has to blink
+            let mut timeout = blink_deadline
+                .saturating_duration_since(Instant::now())
+                .min(CONFIG_POLL_INTERVAL);
+            if self.file_walk.is_some() {
+                timeout = timeout.min(FILE_WALK_POLL_INTERVAL);
+            }
+            let Some(first_event) = event_pump.wait_event_timeout(timeout.as_millis() as u32)
+            else {
+                continue;
+            };
+
+            let events: Vec<Event> = std::iter::once(first_event)
+                .chain(event_pump.poll_iter())
+                .collect();
+            for event in events {
+                if matches!(event, Event::KeyDown { .. }) && self.status.take().is_some() {
+                    needs_redraw = true;
+                }
+                match event {
+                    Event::Quit { .. } => break 'running,
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if self.palette.is_some() => {
+                        skip_text_input = false;
+                        if !self.palette_key(key) {
+                            break 'running;
+                        }
+                        needs_redraw = true;
+                    }
+                    Event::KeyDown {
+                        keycode: Some(key),
+                        keymod,
+                        ..
+                    } => {
+                        let modifiers = Self::modifiers(keymod);
+                        let chord = Chord::new(&key.name(), modifiers);
+                        if chord.is_modifier() {
+                            continue;
+                        }
+                        skip_text_input = false;
+                        if self.tree_focused && self.tree_key(key, modifiers) {
+                            // letters the tree used shouldn't turn up in a
+                            // prompt it opened
+                            skip_text_input = true;
+                            needs_redraw = true;
+                            continue;
+                        }
+                        if self.vim_key(key, modifiers) {
+                            Self::manage_cursor(
+                                &mut time_since_cursor_change,
+                                &mut cursor_state,
+                                true,
+                                &self.config,
+                            );
+                            needs_redraw = true;
+                            continue;
+                        }
+                        match self.keymap.press(chord) {
+                            KeyPress::Command(command) => {
+                                skip_text_input = true;
+                                if !self.run_command(&command) {
+                                    break 'running;
+                                }
+                            }
+                            KeyPress::Pending => skip_text_input = true,
+                            KeyPress::Unbound => continue,
+                        }
+                        Self::manage_cursor(
+                            &mut time_since_cursor_change,
+                            &mut cursor_state,
+                            true,
+                            &self.config,
+                        );
+                        needs_redraw = true;
+                    }
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } if self.screen.tab_at(x, y).is_some() => {
+                        if let Some(index) = self.screen.tab_at(x, y) {
+                            self.switch_buffer(index);
+                        }
+                        needs_redraw = true;
+                    }
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } if self.sidebar_row_at(x, y).is_some() => {
+                        self.tree_focused = true;
+                        if let Some(Some(row)) = self.sidebar_row_at(x, y) {
+                            if let Some(tree) = self.file_tree.as_mut() {
+                                tree.select(row);
+                            }
+                            self.open_tree_row();
+                        }
+                        needs_redraw = true;
+                    }
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } => {
+                        self.tree_focused = false;
+                        if let Some(pane) = self.screen.pane_at(x, y) {
+                            self.focus_pane(pane);
+                        }
+                        // ctrl+click adds another cursor rather than moving the one
+                        // there is, alt+drag selects a rectangle
+                        let modifiers = Self::modifiers(self.sdl_context.keyboard().mod_state());
+                        if modifiers.alt {
+                            if let Some(position) =
+                                self.screen.virtual_position_at(x, y, &self.atlas)
+                            {
+                                self.screen.set_block(position, position, &self.text_buffer);
+                                self.dragging_block = true;
+                            }
+                        } else {
+                            self.screen.cursor_click(
+                                x,
+                                y,
+                                &self.atlas,
+                                modifiers.ctrl,
+                                &self.text_buffer,
+                            );
+                        }
+                        self.update_selection();
+                        Self::manage_cursor(
+                            &mut time_since_cursor_change,
+                            &mut cursor_state,
+                            true,
+                            &self.config,
+                        );
+                        needs_redraw = true;
+                    }
+                    Event::MouseMotion {
+                        mousestate, x, y, ..
+                    } if self.dragging_block && mousestate.left() => {
+                        let head = self.screen.virtual_position_at(x, y, &self.atlas);
+                        if let (Some((anchor, _)), Some(head)) = (self.screen.block(), head) {
+                            self.screen.set_block(anchor, head, &self.text_buffer);
+                            needs_redraw = true;
+                        }
+                    }
+                    Event::MouseButtonUp {
+                        mouse_btn: MouseButton::Left,
+                        ..
+                    } => self.dragging_block = false,
+                    Event::MouseWheel { y, .. } => {
+                        self.screen.scroll(-y * SCROLL_LINES, &self.text_buffer);
+                        needs_redraw = true;
+                    }
+                    Event::TextInput { .. } if skip_text_input => skip_text_input = false,
+                    Event::TextInput { text, .. } if self.palette.is_some() => {
+                        if let Some((_, palette)) = self.palette.as_mut() {
+                            palette.insert(&text);
+                        }
+                        needs_redraw = true;
+                    }
+                    Event::TextInput { .. } if self.tree_focused => {}
+                    Event::TextInput { text, .. } if self.vim.is_some() => {
+                        for character in text.chars() {
+                            self.vim_input(vim::Key::Char(character));
+                        }
+                        Self::manage_cursor(
+                            &mut time_since_cursor_change,
+                            &mut cursor_state,
+                            true,
+                            &self.config,
+                        );
+                        needs_redraw = true;
+                    }
+                    Event::TextInput { text, .. } => {
+                        self.last_command = None;
+                        self.show_undo_tree = false;
+                        self.text_buffer.begin_group();
+                        self.insert_text(&text);
+                        self.text_buffer.end_group();
+                        self.update_selection();
+                        Self::manage_cursor(
+                            &mut time_since_cursor_change,
+                            &mut cursor_state,
+                            true,
+                            &self.config,
+                        );
+                        needs_redraw = true;
+                    }
+                    Event::Window {
+                        win_event: WindowEvent::SizeChanged(width, height),
+                        ..
+                    } => {
+                        self.screen
+                            .resize(width as u32, height as u32, &self.text_buffer);
+                        // moving to a display with a different density needs new glyphs
+                        if self.screen.get_scale() != self.atlas.get_scale() {
+                            self.rebuild_atlas()?;
+                        }
+                        needs_redraw = true;
+                    }
+                    // the window was uncovered or changed, its contents are stale
+                    Event::Window { .. } => needs_redraw = true,
+                    _ => {}
+                }
+            }
+        }
+        // unsaved changes stay in the swap files to be recovered next time
+        self.flush_journals();
+        Ok(())
+    }
+
+    fn modifiers(keymod: Mod) -> Modifiers {
+        Modifiers {
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            super_key: keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD),
+        }
+    }
+
+    // hands the keys vim cares about over to it, returns whether it took the
+    // key. Printable keys arrive as text input and chords go to the keymap
+    fn vim_key(&mut self, key: Keycode, modifiers: Modifiers) -> bool {
+        let Some(mode) = self.vim.as_ref().map(|vim| vim.mode()) else {
+            return false;
+        };
+        if modifiers.ctrl || modifiers.alt || modifiers.super_key {
+            return false;
+        }
+        match key {
+            Keycode::ESCAPE => self.vim_input(vim::Key::Escape),
+            Keycode::BACKSPACE => self.vim_input(vim::Key::Backspace),
+            Keycode::RETURN | Keycode::KP_ENTER => self.vim_input(vim::Key::Enter),
+            Keycode::TAB if mode == vim::Mode::Insert => {
+                for _ in 0..self.config.tab_width {
+                    self.vim_input(vim::Key::Char(' '));
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn vim_input(&mut self, key: vim::Key) {
+        let Some(vim) = self.vim.as_mut() else {
+            return;
+        };
+        let cursor = vim.key(key, &mut self.text_buffer, self.screen.cursor_position());
+        self.screen
+            .set_cursor(cursor.0, cursor.1, &self.text_buffer);
+        self.update_selection();
+    }
+
+    fn update_selection(&mut self) {
+        let cursor = self.screen.cursor_position();
+        let selections = match (&self.vim, self.region()) {
+            (Some(vim), _) => vim.selection(cursor).into_iter().collect(),
+            (None, Some(region)) => vec![region],
+            (None, None) => Vec::new(),
+        };
+        self.screen.set_selections(selections);
+    }
+
+    // returns false when the editor should close
+    fn run_command(&mut self, command: &str) -> bool {
+        // everything a command changes undoes in one go
+        self.text_buffer.begin_group();
+        let running = self.dispatch(command);
+        self.text_buffer.end_group();
+        self.last_command = Some(command.to_string());
+        if !matches!(
+            command,
+            "undo" | "redo" | "undo_older" | "undo_newer" | "undo_tree"
+        ) {
+            self.show_undo_tree = false;
+        }
+        self.update_selection();
+        running
+    }
+
+    fn dispatch(&mut self, command: &str) -> bool {
+        match command {
+            "save" => self.save(),
+            // they'd change the text as much as typing would
+            "undo" | "redo" | "undo_older" | "undo_newer" if self.readonly => return true,
+            "undo" => {
+                if let Some(position) = self.text_buffer.undo() {
+                    self.move_cursor(position);
+                }
+            }
+            "redo" => {
+                if let Some(position) = self.text_buffer.redo() {
+                    self.move_cursor(position);
+                }
+            }
+            "undo_older" => {
+                if let Some(position) = self.text_buffer.travel(-1) {
+                    self.move_cursor(position);
+                }
+            }
+            "undo_newer" => {
+                if let Some(position) = self.text_buffer.travel(1) {
+                    self.move_cursor(position);
+                }
+            }
+            "undo_tree" => self.show_undo_tree = !self.show_undo_tree,
+            "delete_backward" => self.backspace(),
+            "indent" => self.insert_text(&" ".repeat(self.config.tab_width)),
+            "newline" => self.insert_text("\n"),
+            "cursor_left" => self.screen.cursor_move(Keycode::LEFT, &self.text_buffer),
+            "cursor_right" => self.screen.cursor_move(Keycode::RIGHT, &self.text_buffer),
+            "cursor_up" => self.screen.cursor_move(Keycode::UP, &self.text_buffer),
+            "cursor_down" => self.screen.cursor_move(Keycode::DOWN, &self.text_buffer),
+            "line_start" => self.move_cursors(|_, (line, _)| (line, 0)),
+            "line_end" => self.move_cursors(|buffer, (line, _)| (line, buffer.line_len(line))),
+            "smart_home" => self.move_cursors(motion::smart_home),
+            "word_left" => {
+                let word_chars = self.config.word_chars.clone();
+                self.move_cursors(|buffer, position| {
+                    motion::word_left(buffer, position, &word_chars)
+                })
+            }
+            "word_right" => {
+                let word_chars = self.config.word_chars.clone();
+                self.move_cursors(|buffer, position| {
+                    motion::word_right(buffer, position, &word_chars)
+                })
+            }
+            "paragraph_up" => self.move_cursors(motion::paragraph_up),
+            "paragraph_down" => self.move_cursors(motion::paragraph_down),
+            "delete_word_backward" => {
+                let word_chars = self.config.word_chars.clone();
+                self.edit_at_cursors(|buffer, cursor| match cursor.selection() {
+                    Some((start, end)) => (start, end, String::new()),
+                    None => {
+                        let start = motion::word_left(buffer, cursor.position, &word_chars);
+                        (start, cursor.position, String::new())
+                    }
+                })
+            }
+            "delete_word_forward" => {
+                let word_chars = self.config.word_chars.clone();
+                self.edit_at_cursors(|buffer, cursor| match cursor.selection() {
+                    Some((start, end)) => (start, end, String::new()),
+                    None => {
+                        let end = motion::word_right(buffer, cursor.position, &word_chars);
+                        (cursor.position, end, String::new())
+                    }
+                })
+            }
+            "kill_line" => self.kill_line(),
+            "kill_region" => self.kill_region(true),
+            "copy_region" => self.kill_region(false),
+            "yank" => self.yank(),
+            "yank_pop" => self.yank_pop(),
+            "set_mark" => self.mark = Some(self.screen.cursor_position()),
+            "cancel" => self.mark = None,
+            "add_next_occurrence" => self.add_next_occurrence(),
+            "add_cursor_above" => self.add_cursor_vertically(-1),
+            "add_cursor_below" => self.add_cursor_vertically(1),
+            "block_left" => self.extend_block(0, -1),
+            "block_right" => self.extend_block(0, 1),
+            "single_cursor" => {
+                let position = self.screen.cursor_position();
+                self.move_cursor(position);
+            }
+            "next_buffer" => self.switch_buffer((self.active + 1) % (self.tabs.len() + 1)),
+            "previous_buffer" => {
+                let count = self.tabs.len() + 1;
+                self.switch_buffer((self.active + count - 1) % count)
+            }
+            "find_file" => match std::env::current_dir() {
+                Ok(root) => {
+                    self.file_walk = Some(FileWalk::start(root.clone()));
+                    self.palette = Some((
+                        PaletteKind::Files(root),
+                        Palette::new("Find file:", Vec::new()),
+                    ));
+                }
+                Err(e) => eprintln!("error: could not read the working directory: {}", e),
+            },
+            "open_file" => {
+                self.palette = Some((
+                    PaletteKind::OpenFile,
+                    Palette::new("Open file:", Vec::new()),
+                ))
+            }
+            "close_buffer" if self.text_buffer.is_modified() => {
+                let items = CLOSE_CHOICES
+                    .iter()
+                    .map(|(label, detail)| Item {
+                        label: label.to_string(),
+                        detail: detail.to_string(),
+                    })
+                    .collect();
+                let prompt = format!("{} has unsaved changes:", self.buffer_name());
+                self.palette = Some((PaletteKind::CloseUnsaved, Palette::new(&prompt, items)));
+            }
+            "close_buffer" => return self.close_buffer(),
+            "toggle_file_tree" => match self.file_tree {
+                Some(_) => self.close_file_tree(),
+                None => {
+                    self.open_file_tree();
+                    self.tree_focused = self.file_tree.is_some();
+                }
+            },
+            "focus_file_tree" => match self.tree_focused {
+                true => self.tree_focused = false,
+                false => {
+                    if self.file_tree.is_none() {
+                        self.open_file_tree();
+                    }
+                    self.tree_focused = self.file_tree.is_some();
+                }
+            },
+            "new_file" => {
+                if let Some(tree) = self.open_file_tree() {
+                    let dir = tree.selected_dir();
+                    let mut palette =
+                        Palette::new("New file (end with / for a directory):", Vec::new());
+                    if !dir.as_os_str().is_empty() {
+                        palette.insert(&format!("{}/", Self::slashed(&dir)));
+                    }
+                    self.palette = Some((PaletteKind::NewFile, palette));
+                }
+            }
+            "rename_file" => {
+                let selected = self
+                    .open_file_tree()
+                    .and_then(|tree| tree.selected_row())
+                    .map(|row| row.path.clone());
+                if let Some(path) = selected {
+                    let mut palette =
+                        Palette::new(&format!("Rename {} to:", path.display()), Vec::new());
+                    palette.insert(&Self::slashed(&path));
+                    self.palette = Some((PaletteKind::RenameFile(path), palette));
+                }
+            }
+            "delete_file" => {
+                let selected = self
+                    .open_file_tree()
+                    .and_then(|tree| tree.selected_row())
+                    .map(|row| row.path.clone());
+                if let Some(path) = selected {
+                    let items = DELETE_CHOICES
+                        .iter()
+                        .map(|(label, detail)| Item {
+                            label: label.to_string(),
+                            detail: detail.to_string(),
+                        })
+                        .collect();
+                    let prompt = format!("Delete {}?", path.display());
+                    self.palette =
+                        Some((PaletteKind::DeleteFile(path), Palette::new(&prompt, items)));
+                }
+            }
+            "toggle_wrap" => self.screen.toggle_wrap(),
+            "split_right" => self.screen.split(Side::Right, &self.text_buffer),
+            "split_down" => self.screen.split(Side::Down, &self.text_buffer),
+            "close_pane" => {
+                let view = self.screen.view();
+                if self.screen.close_pane() {
+                    self.sync_buffer(view);
+                }
+            }
+            "focus_pane_left" => self.focus_side(Side::Left),
+            "focus_pane_right" => self.focus_side(Side::Right),
+            "focus_pane_up" => self.focus_side(Side::Up),
+            "focus_pane_down" => self.focus_side(Side::Down),
+            "focus_next_pane" => {
+                let next = (self.screen.focused_pane() + 1) % self.screen.pane_count();
+                self.focus_pane(next);
+            }
+            "goto_line" => {
+                self.palette = Some((
+                    PaletteKind::GotoLine,
+                    Palette::new("Go to line:", Vec::new()),
+                ))
+            }
+            "command_palette" => {
+                let items = COMMANDS
+                    .iter()
+                    .map(|command| Item {
+                        label: command.name.to_string(),
+                        detail: command.description.to_string(),
+                    })
+                    .collect();
+                self.palette = Some((PaletteKind::Commands, Palette::new(">", items)));
+            }
+            "quit" => return false,
+            _ => eprintln!("unknown command '{}'", command),
+        }
+        true
+    }
+
+    // markdown files show their emphasis, everything else is plain
+    fn styles_for(path: &Path, line_num: u32, line: &str) -> Vec<StyledRun> {
+        if !markdown::is_markdown(path) {
+            return Vec::new();
+        }
+        markdown::emphasis(line)
+            .into_iter()
+            .map(|emphasis| StyledRun {
+                line: line_num,
+                start: emphasis.start,
+                end: emphasis.end,
+                style: match (emphasis.bold, emphasis.italic) {
+                    (true, true) => Style::BoldItalic,
+                    (true, false) => Style::Bold,
+                    (false, true) => Style::Italic,
+                    (false, false) => Style::Regular,
+                },
+            })
+            .collect()
+    }
+
+    fn draw(&mut self, cursor_state: &CursorState) {
+        self.screen
+            .set_cursor_visible(*cursor_state == CursorState::On);
+        let changes = self.text_buffer.take_changes();
+        self.screen
+            .buffer_changed(self.text_buffer.path(), &changes);
+        let (text_buffer, tabs) = (&self.text_buffer, &self.tabs);
+        self.screen.draw_text(
+            |path| Self::find_buffer(text_buffer, tabs, path),
+            Self::styles_for,
+            &self.atlas,
+        );
+        let mut tabs: Vec<(String, bool)> = self
+            .tabs
+            .iter()
+            .map(|tab| (Self::display_name(&tab.buffer), tab.buffer.is_modified()))
+            .collect();
+        tabs.insert(
+            self.active,
+            (self.buffer_name(), self.text_buffer.is_modified()),
+        );
+        self.screen.draw_tabs(&tabs, self.active, &self.atlas);
+        if let Some(tree) = &self.file_tree {
             self.screen
-                .draw_text(&mut self.text_buffer, self.surface.as_ref(), &self.atlas);
-
-            let ctrl_pressed = event_pump
-                .keyboard_state()
-                .pressed_scancodes()
-                .filter_map(Keycode::from_scancode)
-                .any(|key| key == Keycode::LCTRL || key == Keycode::RCTRL);
-
-            for event in event_pump.poll_iter() {
-                if ctrl_pressed {
-                    if let Event::KeyDown { keycode, .. } = event {
-                        if let Some(key) = keycode {
-                            match key {
-                                Keycode::S => println!("Save file"),
-                                Keycode::LEFTBRACKET => println!("redo last operation in history"),
-                                Keycode::RIGHTBRACKET => println!("undo last operation in history"),
-                                _ => println!("World"),
+                .draw_sidebar(tree, self.tree_focused, &self.atlas);
+        }
+        if let Some(status) = &self.status {
+            self.screen.draw_status(status, &self.atlas);
+        } else if let Some(vim) = &self.vim {
+            self.screen.draw_status(&vim.indicator(), &self.atlas);
+        }
+        if self.show_undo_tree {
+            self.screen
+                .draw_undo_tree(self.text_buffer.undo_tree(), &self.atlas);
+        }
+        if let Some((_, palette)) = &self.palette {
+            self.screen.draw_palette(palette, &self.atlas);
+        }
+        self.screen.render();
+    }
+
+    // handles a key while the palette is open, returns false when the editor
+    // should close
+    fn palette_key(&mut self, key: Keycode) -> bool {
+        let Some((_, palette)) = self.palette.as_mut() else {
+            return true;
+        };
+        match key {
+            Keycode::ESCAPE => {
+                // not answering about a changed file keeps the changes here,
+                // or it would only be asked again
+                match self.palette.take() {
+                    Some((PaletteKind::ChangedOnDisk(path), _)) => {
+                        if let Some(buffer) = self.find_buffer_mut(&path) {
+                            buffer.ignore_disk_changes();
+                        }
+                    }
+                    // not answering about a swap file keeps it for later
+                    // and goes on to the next one
+                    Some((PaletteKind::Recover(path), _)) => {
+                        if let Some(buffer) = self.find_buffer_mut(&path) {
+                            if let Err(e) = buffer.set_swap_aside() {
+                                eprintln!("error: {}", e);
                             }
                         }
+                        self.file_walk = None;
+                        self.offer_recovery();
+                        return true;
                     }
-                } else {
-                    match event {
-                        Event::Quit { .. } => break 'running,
-                        Event::KeyDown { keycode, .. } => {
-                            if let Some(key) = keycode {
-                                match key {
-                                    Keycode::BACKSPACE => println!("Backspace"),
-                                    Keycode::TAB => println!("TAB"),
-                                    Keycode::RETURN => println!("Return"),
-                                    Keycode::UP
-                                    | Keycode::DOWN
-                                    | Keycode::LEFT
-                                    | Keycode::RIGHT => {
-                                        self.screen.cursor_move(key);
-                                        Self::manage_cursor(
-                                            &mut time_since_cursor_change,
-                                            &mut cursor_state,
-                                            true,
-                                        );
+                    _ => {}
+                }
+                self.file_walk = None;
+            }
+            Keycode::UP => palette.move_selection(-1),
+            Keycode::DOWN => palette.move_selection(1),
+            Keycode::BACKSPACE => palette.backspace(),
+            Keycode::RETURN | Keycode::KP_ENTER => {
+                let (kind, palette) = self.palette.take().unwrap();
+                self.file_walk = None;
+                match kind {
+                    PaletteKind::Commands => {
+                        if let Some(item) = palette.selected_item() {
+                            return self.run_command(COMMANDS[item].name);
+                        }
+                    }
+                    PaletteKind::OpenFile => {
+                        let path = PathBuf::from(palette.query().trim());
+                        match Self::open_file(&path) {
+                            Ok(buffer) => self.add_buffer(buffer),
+                            Err(e) => eprintln!("error: {}", e),
+                        }
+                    }
+                    PaletteKind::Files(root) => {
+                        if let Some(item) = palette.selected_item() {
+                            let path = root.join(&palette.items()[item].label);
+                            match Self::open_file(&path) {
+                                Ok(buffer) => self.add_buffer(buffer),
+                                Err(e) => eprintln!("error: {}", e),
+                            }
+                        }
+                    }
+                    PaletteKind::CloseUnsaved => match palette.selected_item() {
+                        Some(0) => {
+                            self.save();
+                            if !self.text_buffer.is_modified() {
+                                return self.close_buffer();
+                            }
+                        }
+                        Some(1) => {
+                            self.text_buffer.discard_swap();
+                            return self.close_buffer();
+                        }
+                        _ => {}
+                    },
+                    PaletteKind::NewFile => {
+                        let name = palette.query().trim();
+                        if let Some(tree) = self.file_tree.as_mut() {
+                            let path = Path::new(name.trim_end_matches('/'));
+                            match tree.create(path, name.ends_with('/')) {
+                                Ok(()) if !name.ends_with('/') => {
+                                    self.open_tree_row();
+                                }
+                                Ok(()) => {}
+                                Err(e) => eprintln!("error: {}", e),
+                            }
+                        }
+                    }
+                    PaletteKind::RenameFile(from) => {
+                        let to = PathBuf::from(palette.query().trim().trim_end_matches('/'));
+                        if let Some(tree) = self.file_tree.as_mut() {
+                            let root = tree.root().to_path_buf();
+                            match tree.rename(&from, &to) {
+                                Ok(()) => self.buffers_renamed(&root.join(from), &root.join(to)),
+                                Err(e) => eprintln!("error: {}", e),
+                            }
+                        }
+                    }
+                    // buffers of a deleted file stay open, saving one writes
+                    // it back
+                    PaletteKind::DeleteFile(path) => {
+                        if let (Some(0), Some(tree)) =
+                            (palette.selected_item(), self.file_tree.as_mut())
+                        {
+                            if let Err(e) = tree.delete(&path) {
+                                eprintln!("error: {}", e);
+                            }
+                        }
+                    }
+                    PaletteKind::Recover(path) => {
+                        match palette.selected_item() {
+                            Some(0) => {
+                                if let Some(buffer) = self.find_buffer_mut(&path) {
+                                    match buffer.recover() {
+                                        Ok(()) => self.buffer_edited(&path),
+                                        // it won't recover any better next time, so
+                                        // it's moved out of the way like an unanswered one
+                                        Err(e) => {
+                                            let set_aside = buffer.set_swap_aside();
+                                            let mut status = format!(
+                                                "could not recover {}: {}",
+                                                path.display(),
+                                                e
+                                            );
+                                            if let Err(e) = set_aside {
+                                                status = format!("{status}, {e}");
+                                            }
+                                            eprintln!("error: {}", status);
+                                            self.status = Some(status);
+                                        }
                                     }
-                                    _ => println!("Other keycode"),
                                 }
                             }
+                            Some(1) => {
+                                if let Some(buffer) = self.find_buffer_mut(&path) {
+                                    buffer.discard_swap();
+                                }
+                            }
+                            _ => {}
                         }
-                        Event::TextInput { text, .. } => println!("{}", text),
-                        _ => {}
+                        self.offer_recovery();
                     }
+                    PaletteKind::ChangedOnDisk(path) => match palette.selected_item() {
+                        Some(0) => self.reload_buffer(&path),
+                        Some(1) => {
+                            if let Some(buffer) = self.find_buffer_mut(&path) {
+                                buffer.ignore_disk_changes();
+                            }
+                        }
+                        Some(2) => self.show_disk_diff(&path),
+                        _ => {}
+                    },
+                    PaletteKind::GotoLine => match palette.query().trim().parse::<u32>() {
+                        Ok(line) if line > 0 => {
+                            self.screen.set_cursor(line - 1, 0, &self.text_buffer)
+                        }
+                        _ => eprintln!("'{}' is not a line number", palette.query()),
+                    },
                 }
             }
-            if cursor_state == CursorState::On {
-                self.screen.draw_cursor(&self.atlas);
+            _ => {}
+        }
+        true
+    }
+
+    // reloads buffers whose file inotify saw something else write, asking
+    // first when there are unsaved changes that would be lost. Returns
+    // whether anything needs drawing again
+    fn check_files_on_disk(&mut self) -> bool {
+        let Some(watcher) = self.file_watcher.as_mut() else {
+            return false;
+        };
+        let buffers =
+            std::iter::once(&self.text_buffer).chain(self.tabs.iter().map(|tab| &tab.buffer));
+        for buffer in buffers {
+            watcher.watch(buffer.path());
+        }
+        for path in watcher.changed() {
+            if !self.written.contains(&path) {
+                self.written.push(path);
             }
-            self.screen.render();
         }
-        Ok(())
+        if self.written.is_empty() {
+            return false;
+        }
+
+        let buffers = std::iter::once(&mut self.text_buffer)
+            .chain(self.tabs.iter_mut().map(|tab| &mut tab.buffer));
+        let mut changed = Vec::new();
+        let mut unsaved = Vec::new();
+        for buffer in buffers {
+            if !self.written.iter().any(|path| path == buffer.path()) || !buffer.changed_on_disk() {
+                continue;
+            }
+            match buffer.is_modified() {
+                true => unsaved.push(buffer.path().to_path_buf()),
+                false => changed.push(buffer.path().to_path_buf()),
+            }
+        }
+        // the ones with unsaved changes wait until they can be asked about
+        self.written = unsaved;
+        for path in &changed {
+            self.reload_buffer(path);
+        }
+        // one question at a time, the next is asked once it's answered
+        if self.palette.is_none() && !self.written.is_empty() {
+            let path = self.written.remove(0);
+            let items = CHANGED_CHOICES
+                .iter()
+                .map(|(label, detail)| Item {
+                    label: label.to_string(),
+                    detail: detail.to_string(),
+                })
+                .collect();
+            let prompt = format!(
+                "{} changed on disk and has unsaved changes:",
+                path.display()
+            );
+            self.palette = Some((
+                PaletteKind::ChangedOnDisk(path),
+                Palette::new(&prompt, items),
+            ));
+            return true;
+        }
+        !changed.is_empty()
+    }
+
+    fn reload_buffer(&mut self, path: &Path) {
+        let Some(buffer) = self.find_buffer_mut(path) else {
+            return;
+        };
+        if let Err(e) = buffer.reload() {
+            eprintln!("error: could not reload {}: {}", path.display(), e);
+            return;
+        }
+        self.buffer_edited(path);
+    }
+
+    // a buffer changed other than through its cursors, the panes showing it
+    // catch up
+    fn buffer_edited(&mut self, path: &Path) {
+        // the buffer on screen has its changes picked up when it's drawn
+        if self.text_buffer.path() == path {
+            let cursors = self.screen.cursors().to_vec();
+            self.screen.replace_cursors(cursors, &self.text_buffer);
+        } else if let Some(buffer) = self.find_buffer_mut(path) {
+            let changes = buffer.take_changes();
+            self.screen.buffer_changed(path, &changes);
+        }
+    }
+
+    // asks about the first open buffer that has a swap file left over, the
+    // rest get asked about once that's answered
+    fn offer_recovery(&mut self) {
+        if self.palette.is_some() || self.readonly {
+            return;
+        }
+        let Some(path) = std::iter::once(&self.text_buffer)
+            .chain(self.tabs.iter().map(|tab| &tab.buffer))
+            .find(|buffer| buffer.has_swap())
+            .map(|buffer| buffer.path().to_path_buf())
+        else {
+            return;
+        };
+        let items = RECOVER_CHOICES
+            .iter()
+            .map(|(label, detail)| Item {
+                label: label.to_string(),
+                detail: detail.to_string(),
+            })
+            .collect();
+        let prompt = format!(
+            "{} has unsaved changes from a session that didn't close:",
+            path.display()
+        );
+        self.palette = Some((PaletteKind::Recover(path), Palette::new(&prompt, items)));
+    }
+
+    // a swap file that can't be written is reported, editing carries on
+    // without it
+    fn flush_journals(&mut self) {
+        let buffers = std::iter::once(&mut self.text_buffer)
+            .chain(self.tabs.iter_mut().map(|tab| &mut tab.buffer));
+        for buffer in buffers {
+            if let Err(e) = buffer.flush_journal() {
+                eprintln!("error: {}", e);
+            }
+        }
+    }
+
+    // opens a tab with what changed between the file on disk and the buffer
+    fn show_disk_diff(&mut self, path: &Path) {
+        let Some(buffer) = self.find_buffer_mut(path) else {
+            return;
+        };
+        let on_disk = match std::fs::read(path) {
+            Ok(text) => String::from_utf8_lossy(&text).into_owned(),
+            Err(e) => {
+                eprintln!("error: could not read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        buffer.ignore_disk_changes();
+        let name = path.display().to_string();
+        let text = diff::unified(
+            &on_disk,
+            &buffer.text(),
+            &format!("{name} (on disk)"),
+            &format!("{name} (unsaved)"),
+        );
+        let name = PathBuf::from(format!("{name} (diff)"));
+        self.add_buffer(Buffer::scratch(name, text.into_bytes()));
+    }
+
+    // shows the file tree at the working directory with the buffer on screen
+    // selected, returns it
+    fn open_file_tree(&mut self) -> Option<&mut FileTree> {
+        if self.file_tree.is_none() {
+            let root = match std::env::current_dir() {
+                Ok(root) => root,
+                Err(e) => {
+                    eprintln!("error: could not read the working directory: {}", e);
+                    return None;
+                }
+            };
+            let mut tree = FileTree::new(root);
+            if let Ok(path) = std::path::absolute(self.text_buffer.path()) {
+                if let Ok(relative) = path.strip_prefix(tree.root()) {
+                    tree.reveal(relative);
+                }
+            }
+            self.file_tree = Some(tree);
+            self.screen.set_sidebar_visible(true);
+        }
+        self.file_tree.as_mut()
+    }
+
+    fn close_file_tree(&mut self) {
+        self.file_tree = None;
+        self.tree_focused = false;
+        self.screen.set_sidebar_visible(false);
+    }
+
+    // handles a key while the file tree has the focus, returns whether it
+    // took the key. Chords with ctrl, alt or super go to the keymap so
+    // commands still work
+    fn tree_key(&mut self, key: Keycode, modifiers: Modifiers) -> bool {
+        if modifiers.ctrl || modifiers.alt || modifiers.super_key {
+            return false;
+        }
+        let Some(tree) = self.file_tree.as_mut() else {
+            return false;
+        };
+        match key {
+            Keycode::UP => tree.move_selection(-1),
+            Keycode::DOWN => tree.move_selection(1),
+            Keycode::PAGEUP => tree.move_selection(-10),
+            Keycode::PAGEDOWN => tree.move_selection(10),
+            Keycode::LEFT => tree.collapse(),
+            Keycode::RIGHT => tree.expand(),
+            Keycode::RETURN | Keycode::KP_ENTER => self.open_tree_row(),
+            Keycode::ESCAPE => self.tree_focused = false,
+            Keycode::N => {
+                self.run_command("new_file");
+            }
+            Keycode::F2 | Keycode::R => {
+                self.run_command("rename_file");
+            }
+            Keycode::DELETE | Keycode::D => {
+                self.run_command("delete_file");
+            }
+            // anything else would type into the text behind the tree
+            _ => {}
+        }
+        true
+    }
+
+    // a directory opens or closes, a file opens in a tab and takes the focus
+    fn open_tree_row(&mut self) {
+        let Some(tree) = self.file_tree.as_mut() else {
+            return;
+        };
+        let Some(row) = tree.selected_row() else {
+            return;
+        };
+        if row.is_dir {
+            tree.toggle(tree.selected());
+            return;
+        }
+        let path = tree.root().join(&row.path);
+        match Self::open_file(&path) {
+            Ok(buffer) => {
+                self.add_buffer(buffer);
+                self.tree_focused = false;
+            }
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+
+    fn sidebar_row_at(&self, x: i32, y: i32) -> Option<Option<usize>> {
+        let tree = self.file_tree.as_ref()?;
+        self.screen.sidebar_row_at(x, y, tree.rows().len())
+    }
+
+    // open buffers follow a file, or a directory they're in, that was renamed
+    fn buffers_renamed(&mut self, from: &Path, to: &Path) {
+        let buffers = std::iter::once(&mut self.text_buffer)
+            .chain(self.tabs.iter_mut().map(|tab| &mut tab.buffer));
+        let mut renamed = Vec::new();
+        for buffer in buffers {
+            let Ok(path) = std::path::absolute(buffer.path()) else {
+                continue;
+            };
+            let Ok(rest) = path.strip_prefix(from) else {
+                continue;
+            };
+            let new_path = match rest.as_os_str().is_empty() {
+                true => to.to_path_buf(),
+                false => to.join(rest),
+            };
+            renamed.push((buffer.path().to_path_buf(), new_path.clone()));
+            buffer.set_path(new_path);
+        }
+        for (old_path, new_path) in renamed {
+            self.screen.buffer_renamed(&old_path, &new_path);
+        }
+    }
+
+    // a relative path with / between its parts whatever the platform
+    fn slashed(path: &Path) -> String {
+        path.iter()
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    // moves what the file finder's walk found since last time into its
+    // palette, returns whether anything turned up
+    fn poll_file_walk(&mut self) -> bool {
+        let Some(walk) = self.file_walk.as_mut() else {
+            return false;
+        };
+        let found = walk.found();
+        if walk.is_done() {
+            self.file_walk = None;
+        }
+        let Some((PaletteKind::Files(_), palette)) = self.palette.as_mut() else {
+            return false;
+        };
+        let items: Vec<Item> = found
+            .iter()
+            .map(|path| Item {
+                label: Self::slashed(path),
+                detail: String::new(),
+            })
+            .collect();
+        let any = !items.is_empty();
+        palette.extend(items);
+        any
+    }
+
+    // a file that's already open is switched to rather than opened twice
+    fn add_buffer(&mut self, buffer: Buffer) {
+        let same_file = |path: &Path| {
+            path == buffer.path()
+                || path.canonicalize().ok().is_some_and(|path| {
+                    buffer.path().canonicalize().ok().as_deref() == Some(path.as_path())
+                })
+        };
+        if same_file(self.text_buffer.path()) {
+            return;
+        }
+        if let Some(index) = self
+            .tabs
+            .iter()
+            .position(|tab| same_file(tab.buffer.path()))
+        {
+            let index = match index < self.active {
+                true => index,
+                false => index + 1,
+            };
+            self.switch_buffer(index);
+            return;
+        }
+        let previous = Tab {
+            buffer: std::mem::replace(&mut self.text_buffer, buffer),
+            view: self.screen.view(),
+        };
+        self.tabs.insert(self.active, previous);
+        self.active += 1;
+        self.show_buffer(View::with_cursor(0, 0));
+        self.offer_recovery();
+    }
+
+    // index is a tab position counting the buffer on screen
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.active || index > self.tabs.len() {
+            return;
+        }
+        let view = self.swap_buffer(index, self.screen.view());
+        self.show_buffer(view);
+    }
+
+    // brings the buffer at a tab position into text_buffer, the one there
+    // goes back among the tabs keeping previous_view. Returns the view the
+    // buffer brought in was left with
+    fn swap_buffer(&mut self, index: usize, previous_view: View) -> View {
+        let tab = match index > self.active {
+            true => self.tabs.remove(index - 1),
+            false => self.tabs.remove(index),
+        };
+        let previous = Tab {
+            buffer: std::mem::replace(&mut self.text_buffer, tab.buffer),
+            view: previous_view,
+        };
+        // the tabs closed up around the one taken out
+        let previous_index = match index > self.active {
+            true => self.active,
+            false => self.active - 1,
+        };
+        self.tabs.insert(previous_index, previous);
+        self.active = index;
+        tab.view
+    }
+
+    // returns false when the last buffer was closed and the editor should too
+    fn close_buffer(&mut self) -> bool {
+        if self.tabs.is_empty() {
+            return false;
+        }
+        let closed = self.text_buffer.path().to_path_buf();
+        // the tab to the right takes its place, or the one to the left at the end
+        self.active = self.active.min(self.tabs.len() - 1);
+        let tab = self.tabs.remove(self.active);
+        self.text_buffer = tab.buffer;
+        self.show_buffer(tab.view);
+        self.screen.replace_buffer(&closed, &self.text_buffer);
+        true
+    }
+
+    // shows a buffer that was just brought into text_buffer in the focused pane
+    fn show_buffer(&mut self, view: View) {
+        self.forget_buffer_state();
+        self.screen.set_view(view, &self.text_buffer);
+    }
+
+    // state tied to the buffer that was on screen doesn't carry over
+    fn forget_buffer_state(&mut self) {
+        self.mark = None;
+        self.last_yank = None;
+        self.dragging_block = false;
+        if let Some(vim) = self.vim.as_mut() {
+            *vim = Vim::new(self.readonly, &self.config.word_chars);
+        }
+    }
+
+    fn focus_side(&mut self, side: Side) {
+        if let Some(pane) = self.screen.neighbour(side) {
+            self.focus_pane(pane);
+        }
+    }
+
+    fn focus_pane(&mut self, pane: usize) {
+        let view = self.screen.view();
+        if self.screen.focus(pane) {
+            self.sync_buffer(view);
+        }
+    }
+
+    // after the focus moved to another pane text_buffer has to be the buffer
+    // it shows. previous_view is how the buffer that was focused was left
+    fn sync_buffer(&mut self, previous_view: View) {
+        let path = self.screen.buffer_path();
+        if path != self.text_buffer.path() {
+            if let Some(index) = self.tabs.iter().position(|tab| tab.buffer.path() == path) {
+                let index = match index < self.active {
+                    true => index,
+                    false => index + 1,
+                };
+                self.swap_buffer(index, previous_view);
+                self.forget_buffer_state();
+            }
+        }
+        // edits made through another pane can leave its cursors past the end
+        let cursors = self.screen.cursors().to_vec();
+        self.screen.replace_cursors(cursors, &self.text_buffer);
+    }
+
+    fn find_buffer<'a>(
+        text_buffer: &'a Buffer,
+        tabs: &'a [Tab],
+        path: &Path,
+    ) -> Option<&'a Buffer> {
+        match text_buffer.path() == path {
+            true => Some(text_buffer),
+            false => tabs
+                .iter()
+                .map(|tab| &tab.buffer)
+                .find(|buffer| buffer.path() == path),
+        }
+    }
+
+    fn find_buffer_mut(&mut self, path: &Path) -> Option<&mut Buffer> {
+        match self.text_buffer.path() == path {
+            true => Some(&mut self.text_buffer),
+            false => self
+                .tabs
+                .iter_mut()
+                .map(|tab| &mut tab.buffer)
+                .find(|buffer| buffer.path() == path),
+        }
+    }
+
+    fn buffer_name(&self) -> String {
+        Self::display_name(&self.text_buffer)
+    }
+
+    fn display_name(buffer: &Buffer) -> String {
+        buffer
+            .path()
+            .file_name()
+            .unwrap_or(buffer.path().as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn move_cursor(&mut self, (line, column): (u32, u32)) {
+        self.screen.set_cursor(line, column, &self.text_buffer);
+    }
+
+    // moves every cursor, dropping their selections
+    fn move_cursors<F>(&mut self, motion: F)
+    where
+        F: Fn(&Buffer, (u32, u32)) -> (u32, u32),
+    {
+        let cursors = self
+            .screen
+            .cursors()
+            .iter()
+            .map(|cursor| Cursor::new(motion(&self.text_buffer, cursor.position)))
+            .collect();
+        self.screen.replace_cursors(cursors, &self.text_buffer);
+    }
+
+    // makes the same change at every cursor. The edit gives the range to
+    // replace and what to put there, all worked out before the buffer
+    // changes, then each cursor ends up after its inserted text
+    fn edit_at_cursors<F>(&mut self, mut edit: F)
+    where
+        F: FnMut(&Buffer, &Cursor) -> ((u32, u32), (u32, u32), String),
+    {
+        if self.readonly {
+            return;
+        }
+        let edits: Vec<(usize, usize, String)> = self
+            .screen
+            .cursors()
+            .iter()
+            .map(|cursor| {
+                let (start, end, text) = edit(&self.text_buffer, cursor);
+                let start = self.text_buffer.offset_of(start.0, start.1);
+                let end = self.text_buffer.offset_of(end.0, end.1);
+                (start, end, text)
+            })
+            .collect();
+        // cursors are in buffer order so earlier edits only shift later ones
+        let mut shift = 0isize;
+        let mut previous_end = 0;
+        let mut offsets = Vec::with_capacity(edits.len());
+        self.text_buffer.begin_group();
+        for (start, end, text) in edits {
+            // a range running into the one before it is cut short
+            let start = start.max(previous_end);
+            let end = end.max(start);
+            previous_end = end;
+            let offset = (start as isize + shift) as usize;
+            let (line, column) = self.text_buffer.position_of(offset);
+            if end > start {
+                self.text_buffer.delete(line, column, end - start);
+            }
+            if !text.is_empty() {
+                self.text_buffer.insert(line, column, &text);
+            }
+            shift += text.len() as isize - (end - start) as isize;
+            offsets.push(offset + text.len());
+        }
+        self.text_buffer.end_group();
+        let cursors = offsets
+            .into_iter()
+            .map(|offset| Cursor::new(self.text_buffer.position_of(offset)))
+            .collect();
+        self.screen.replace_cursors(cursors, &self.text_buffer);
+    }
+
+    // selects the word at each cursor that has no selection, once they all
+    // do a new cursor goes on the next match of the primary one's selection
+    fn add_next_occurrence(&mut self) {
+        let cursors = self.screen.cursors();
+        if cursors.iter().any(|cursor| cursor.selection().is_none()) {
+            let cursors = cursors
+                .iter()
+                .map(|cursor| {
+                    let word = cursor.selection().or_else(|| {
+                        motion::word_at(&self.text_buffer, cursor.position, &self.config.word_chars)
+                    });
+                    match word {
+                        Some((start, end)) => Cursor::with_anchor(end, start),
+                        None => *cursor,
+                    }
+                })
+                .collect();
+            self.screen.replace_cursors(cursors, &self.text_buffer);
+            return;
+        }
+        let Some((start, end)) = self.screen.primary_cursor().selection() else {
+            return;
+        };
+        let needle = self.text_buffer.get_range(start, end);
+        let from = self.text_buffer.offset_of(end.0, end.1);
+        if let Some(offset) = self.text_buffer.find(&needle, from) {
+            let start = self.text_buffer.position_of(offset);
+            let end = self.text_buffer.position_of(offset + needle.len());
+            self.screen
+                .add_cursor(Cursor::with_anchor(end, start), &self.text_buffer);
+        }
+    }
+
+    // moves the moving corner of the column selection, starting one at the
+    // primary cursor when there isn't one. It can't go further right than
+    // the longest line it covers
+    fn extend_block(&mut self, lines: i32, columns: i32) {
+        let (anchor, head) = self.screen.block().unwrap_or_else(|| {
+            let position = self.screen.cursor_position();
+            (position, position)
+        });
+        let last_line = self.text_buffer.line_count() - 1;
+        let line = head.0.saturating_add_signed(lines).min(last_line);
+        let widest = (anchor.0.min(line)..=anchor.0.max(line))
+            .map(|line| self.text_buffer.line_len(line))
+            .max()
+            .unwrap_or(0);
+        let column = head
+            .1
+            .saturating_add_signed(columns)
+            .min(widest.max(anchor.1));
+        self.screen
+            .set_block(anchor, (line, column), &self.text_buffer);
+    }
+
+    // puts a new cursor a line above or below the primary one, keeping to
+    // its column where the line is long enough. With a column selection
+    // going it grows that instead, so alt+shift+arrows work the same way
+    // either way
+    fn add_cursor_vertically(&mut self, direction: i32) {
+        if self.screen.block().is_some() {
+            self.extend_block(direction, 0);
+            return;
+        }
+        let primary = *self.screen.primary_cursor();
+        let line = primary.position.0 as i64 + direction as i64;
+        if line < 0 || line >= self.text_buffer.line_count() as i64 {
+            return;
+        }
+        let line = line as u32;
+        let cursor = Cursor {
+            position: (
+                line,
+                primary.goal_column.min(self.text_buffer.line_len(line)),
+            ),
+            anchor: None,
+            goal_column: primary.goal_column,
+        };
+        self.screen.add_cursor(cursor, &self.text_buffer);
+    }
+
+    // the text between the mark and the cursor, in order
+    fn region(&self) -> Option<((u32, u32), (u32, u32))> {
+        let mark = self.mark?;
+        let cursor = self.screen.cursor_position();
+        Some((mark.min(cursor), mark.max(cursor)))
+    }
+
+    // cuts to the end of the line, or the newline when already there
+    fn kill_line(&mut self) {
+        if self.readonly {
+            return;
+        }
+        let (line, column) = self.screen.cursor_position();
+        let len = self.text_buffer.line_len(line);
+        let end = match column < len {
+            true => (line, len),
+            false if line + 1 < self.text_buffer.line_count() => (line + 1, 0),
+            false => return,
+        };
+        let text = self.text_buffer.get_range((line, column), end);
+        let append = self.last_command.as_deref() == Some("kill_line");
+        self.kill_ring.kill(&text, append);
+        // invalid UTF-8 decodes to a different length than it takes up
+        let len = self.text_buffer.range_len((line, column), end);
+        self.text_buffer.delete(line, column, len);
+        self.screen.set_cursor(line, column, &self.text_buffer);
+    }
+
+    // cuts or copies the region, or without a mark whatever the cursors have
+    // selected, one line each
+    fn kill_region(&mut self, delete: bool) {
+        let Some((start, end)) = self.region() else {
+            self.kill_selections(delete);
+            return;
+        };
+        self.mark = None;
+        self.kill_ring
+            .kill(&self.text_buffer.get_range(start, end), false);
+        if delete && !self.readonly {
+            let len = self.text_buffer.range_len(start, end);
+            self.text_buffer.delete(start.0, start.1, len);
+            self.screen.set_cursor(start.0, start.1, &self.text_buffer);
+        }
+    }
+
+    fn kill_selections(&mut self, delete: bool) {
+        let cursors = self.screen.cursors();
+        if cursors.iter().all(|cursor| cursor.selection().is_none()) {
+            return;
+        }
+        // a column selection keeps its lines that are too short to reach it
+        let selections: Vec<String> = cursors
+            .iter()
+            .filter_map(|cursor| match (cursor.selection(), self.screen.block()) {
+                (Some((start, end)), _) => Some(self.text_buffer.get_range(start, end)),
+                (None, Some(_)) => Some(String::new()),
+                (None, None) => None,
+            })
+            .collect();
+        self.kill_ring.kill(&selections.join("\n"), false);
+        if delete {
+            self.edit_at_cursors(|_, cursor| {
+                let (start, end) = cursor
+                    .selection()
+                    .unwrap_or((cursor.position, cursor.position));
+                (start, end, String::new())
+            });
+        }
+    }
+
+    fn yank(&mut self) {
+        let Some(text) = self.kill_ring.yank().map(str::to_string) else {
+            return;
+        };
+        self.insert_yanked(&text);
+    }
+
+    // only does something straight after a yank, swapping the yanked text
+    // for the kill before it
+    fn yank_pop(&mut self) {
+        if !matches!(self.last_command.as_deref(), Some("yank" | "yank_pop")) {
+            return;
+        }
+        let Some((start, end)) = self.last_yank else {
+            return;
+        };
+        let Some(text) = self.kill_ring.yank_pop().map(str::to_string) else {
+            return;
+        };
+        if self.readonly {
+            return;
+        }
+        let len = self.text_buffer.range_len(start, end);
+        self.text_buffer.delete(start.0, start.1, len);
+        self.screen.set_cursor(start.0, start.1, &self.text_buffer);
+        self.insert_yanked(&text);
+    }
+
+    // text with a line for each cursor, like a copied column selection, is
+    // split between them
+    fn insert_yanked(&mut self, text: &str) {
+        let start = self.screen.cursor_position();
+        let mut lines = text.split('\n');
+        let cursor_count = self.screen.cursors().len();
+        if cursor_count > 1 && text.split('\n').count() == cursor_count {
+            self.edit_at_cursors(|_, cursor| {
+                let (start, end) = cursor
+                    .selection()
+                    .unwrap_or((cursor.position, cursor.position));
+                (start, end, lines.next().unwrap_or_default().to_string())
+            });
+        } else {
+            self.insert_text(text);
+        }
+        self.last_yank = Some((start, self.screen.cursor_position()));
+    }
+
+    fn save(&mut self) {
+        if self.readonly {
+            eprintln!("{} is open read only", self.text_buffer.path().display());
+            return;
+        }
+        if let Err(e) = self.text_buffer.save() {
+            eprintln!(
+                "error: could not save {}: {}",
+                self.text_buffer.path().display(),
+                e
+            );
+        }
+    }
+
+    // types at every cursor, replacing what they have selected
+    fn insert_text(&mut self, text: &str) {
+        self.pad_block();
+        self.edit_at_cursors(|_, cursor| {
+            let (start, end) = cursor
+                .selection()
+                .unwrap_or((cursor.position, cursor.position));
+            (start, end, text.to_string())
+        });
+    }
+
+    // lines of a column selection that stop short of its left edge get
+    // spaces up to it, so text typed into the selection goes in at the same
+    // column on every line
+    fn pad_block(&mut self) {
+        let Some((anchor, head)) = self.screen.block() else {
+            return;
+        };
+        if self.readonly {
+            return;
+        }
+        let column = anchor.1.min(head.1);
+        for line in anchor.0.min(head.0)..=anchor.0.max(head.0) {
+            let len = self.text_buffer.line_len(line);
+            if len < column {
+                self.text_buffer
+                    .insert(line, len, &" ".repeat((column - len) as usize));
+            }
+        }
+        self.screen.set_block(anchor, head, &self.text_buffer);
+    }
+
+    // deletes the selection or the character before each cursor, at the
+    // start of a line that joins it onto the previous one
+    fn backspace(&mut self) {
+        self.edit_at_cursors(|buffer, cursor| {
+            let (line, column) = cursor.position;
+            match cursor.selection() {
+                Some((start, end)) => (start, end, String::new()),
+                None if column > 0 => ((line, column - 1), cursor.position, String::new()),
+                None if line > 0 => (
+                    (line - 1, buffer.line_len(line - 1)),
+                    cursor.position,
+                    String::new(),
+                ),
+                None => (cursor.position, cursor.position, String::new()),
+            }
+        });
     }
 
+    // updates the blink state and returns when it next needs to change
     fn manage_cursor(
         time_since_state_change: &mut Instant,
         cursor_state: &mut CursorState,
         refresh_on_state: bool,
-    ) {
+        config: &Config,
+    ) -> Instant {
         if refresh_on_state {
             *time_since_state_change = Instant::now();
             *cursor_state = CursorState::On;
@@ -141,7 +1942,7 @@ impl<'a> Editor<'a> {
         if now
             .checked_duration_since(*time_since_state_change)
             .unwrap()
-            >= Duration::from_millis(600)
+            >= config.cursor_blink_off
             && *cursor_state == CursorState::Off
         {
             //turn on
@@ -150,12 +1951,17 @@ impl<'a> Editor<'a> {
         } else if now
             .checked_duration_since(*time_since_state_change)
             .unwrap()
-            >= Duration::from_millis(400)
+            >= config.cursor_blink_on
             && *cursor_state == CursorState::On
         {
             // turn off
             *cursor_state = CursorState::Off;
             *time_since_state_change = Instant::now();
         }
+        let blink = match cursor_state {
+            CursorState::On => config.cursor_blink_on,
+            CursorState::Off => config.cursor_blink_off,
+        };
+        *time_since_state_change + blink
     }
 }
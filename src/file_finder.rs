@@ -0,0 +1,265 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+// how many paths the walk collects before handing them over
+const BATCH_SIZE: usize = 256;
+
+// lists the files under a directory on another thread so a big tree doesn't
+// hold up the editor. Paths come back relative to the root, leaving out
+// .git and anything a .gitignore on the way down ignores
+pub struct FileWalk {
+    receiver: Receiver<Vec<PathBuf>>,
+    done: bool,
+}
+
+impl FileWalk {
+    pub fn start(root: PathBuf) -> FileWalk {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut batch = Vec::new();
+            walk(&root, Path::new(""), &mut Ignore::default(), &mut |path| {
+                batch.push(path);
+                match batch.len() >= BATCH_SIZE {
+                    true => sender.send(std::mem::take(&mut batch)).is_ok(),
+                    false => true,
+                }
+            });
+            // nobody listening any more is fine, the finder was closed
+            let _ = sender.send(batch);
+        });
+        FileWalk {
+            receiver,
+            done: false,
+        }
+    }
+
+    // the files found since the last call
+    pub fn found(&mut self) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(batch) => found.extend(batch),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        found
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+// goes through a directory in name order, found returns false to stop early
+fn walk<F>(root: &Path, dir: &Path, ignore: &mut Ignore, found: &mut F) -> bool
+where
+    F: FnMut(PathBuf) -> bool,
+{
+    if let Ok(text) = fs::read_to_string(root.join(dir).join(".gitignore")) {
+        ignore.add(dir, &text);
+    }
+    let Ok(entries) = fs::read_dir(root.join(dir)) else {
+        return true;
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = dir.join(entry.file_name());
+        // symlinks aren't followed into directories so loops can't happen
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let is_dir = file_type.is_dir();
+        if entry.file_name() == ".git" || ignore.is_ignored(&path, is_dir) {
+            continue;
+        }
+        let keep_going = match is_dir {
+            true => walk(root, &path, ignore, found),
+            false if file_type.is_symlink() && !root.join(&path).is_file() => true,
+            false => found(path),
+        };
+        if !keep_going {
+            return false;
+        }
+    }
+    true
+}
+
+// the rules from the .gitignore files seen so far. A rule only applies under
+// the directory its file was in
+#[derive(Default)]
+pub struct Ignore {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    base: PathBuf,
+    pattern: Vec<char>,
+    // a rule starting with ! brings back something an earlier rule ignored
+    negate: bool,
+    // a trailing slash only matches directories
+    dir_only: bool,
+    // a slash anywhere but the end ties the pattern to the base directory,
+    // otherwise it matches names at any depth
+    anchored: bool,
+}
+
+impl Ignore {
+    pub fn add(&mut self, base: &Path, text: &str) {
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negate, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            // a backslash lets a pattern start with # or !
+            let line = line.strip_prefix('\\').unwrap_or(line);
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let line = line.strip_prefix('/').unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            self.rules.push(Rule {
+                base: base.to_path_buf(),
+                pattern: line.chars().collect(),
+                negate,
+                dir_only,
+                anchored,
+            });
+        }
+    }
+
+    // path is relative to the root of the walk, the last rule that matches
+    // decides
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(&rule.base) else {
+                continue;
+            };
+            let text: Vec<char> = match rule.anchored {
+                true => relative
+                    .iter()
+                    .map(|part| part.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+                    .chars()
+                    .collect(),
+                false => relative
+                    .file_name()
+                    .map(|name| name.to_string_lossy().chars().collect())
+                    .unwrap_or_default(),
+            };
+            if glob(&rule.pattern, &text) {
+                ignored = !rule.negate;
+            }
+        }
+        ignored
+    }
+}
+
+// gitignore style wildcards: * and ? stay within one path segment, ** goes
+// across them and [a-z] matches a set of characters
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            glob(rest, text)
+                || (0..text.len()).any(|index| text[index] == '/' && glob(rest, &text[index + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|index| glob(rest, &text[index..])),
+        ['*', rest @ ..] => {
+            let segment_end = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=segment_end).any(|index| glob(rest, &text[index..]))
+        }
+        ['?', rest @ ..] => text.first().is_some_and(|c| *c != '/') && glob(rest, &text[1..]),
+        ['[', rest @ ..] => {
+            let Some(close) = rest.iter().skip(1).position(|c| *c == ']').map(|i| i + 1) else {
+                return text.first() == Some(&'[') && glob(rest, &text[1..]);
+            };
+            let Some(c) = text.first() else {
+                return false;
+            };
+            let (negated, set) = match rest[0] {
+                '!' | '^' => (true, &rest[1..close]),
+                _ => (false, &rest[..close]),
+            };
+            let mut matched = false;
+            let mut index = 0;
+            while index < set.len() {
+                if index + 2 < set.len() && set[index + 1] == '-' {
+                    matched |= set[index] <= *c && *c <= set[index + 2];
+                    index += 3;
+                } else {
+                    matched |= set[index] == *c;
+                    index += 1;
+                }
+            }
+            matched != negated && *c != '/' && glob(&rest[close + 1..], &text[1..])
+        }
+        [expected, rest @ ..] => text.first() == Some(expected) && glob(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{glob, Ignore};
+    use std::path::Path;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob(&pattern, &text)
+    }
+
+    #[test]
+    fn globs() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/main.rs"));
+        assert!(matches("**/target", "a/target"));
+        assert!(matches("logs/**", "logs/a/b"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[!a-c]x", "bx"));
+    }
+
+    #[test]
+    fn gitignore_rules() {
+        let mut ignore = Ignore::default();
+        ignore.add(
+            Path::new(""),
+            "# build output\n/target\n*.log\n!keep.log\nbuild/\ndocs/*.html\n",
+        );
+        ignore.add(Path::new("sub"), "local.txt\n");
+        assert!(ignore.is_ignored(Path::new("target"), true));
+        assert!(!ignore.is_ignored(Path::new("src/target"), true));
+        assert!(ignore.is_ignored(Path::new("src/debug.log"), false));
+        assert!(!ignore.is_ignored(Path::new("keep.log"), false));
+        assert!(ignore.is_ignored(Path::new("a/build"), true));
+        assert!(!ignore.is_ignored(Path::new("a/build"), false));
+        assert!(ignore.is_ignored(Path::new("docs/index.html"), false));
+        assert!(!ignore.is_ignored(Path::new("docs/api/index.html"), false));
+        assert!(ignore.is_ignored(Path::new("sub/deep/local.txt"), false));
+        assert!(!ignore.is_ignored(Path::new("local.txt"), false));
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// a row of the tree as it's shown, paths are relative to the root
+#[derive(Debug, PartialEq, Clone)]
+pub struct Row {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+}
+
+// the project directory as a list of rows, directories only list what's in
+// them once they're expanded
+pub struct FileTree {
+    root: PathBuf,
+    expanded: HashSet<PathBuf>,
+    rows: Vec<Row>,
+    selected: usize,
+}
+
+impl FileTree {
+    pub fn new(root: PathBuf) -> FileTree {
+        let mut tree = FileTree {
+            root,
+            expanded: HashSet::new(),
+            rows: Vec::new(),
+            selected: 0,
+        };
+        tree.refresh();
+        tree
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_row(&self) -> Option<&Row> {
+        self.rows.get(self.selected)
+    }
+
+    // reads the directories again after files changed, keeping the same path
+    // selected when it's still there
+    pub fn refresh(&mut self) {
+        let selected = self.selected_row().map(|row| row.path.clone());
+        self.rows.clear();
+        self.expanded.retain(|path| self.root.join(path).is_dir());
+        let mut rows = Vec::new();
+        self.list(Path::new(""), 0, &mut rows);
+        self.rows = rows;
+        if let Some(path) = selected {
+            self.select_path(&path);
+        }
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    // directories come before files, both in name order. .git is left out
+    fn list(&self, dir: &Path, depth: usize, rows: &mut Vec<Row>) {
+        let Ok(entries) = fs::read_dir(self.root.join(dir)) else {
+            return;
+        };
+        let mut entries: Vec<(bool, PathBuf)> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() != ".git")
+            .map(|entry| {
+                let path = dir.join(entry.file_name());
+                (self.root.join(&path).is_dir(), path)
+            })
+            .collect();
+        entries.sort_by(|(a_dir, a), (b_dir, b)| b_dir.cmp(a_dir).then(a.cmp(b)));
+        for (is_dir, path) in entries {
+            let expanded = is_dir && self.expanded.contains(&path);
+            rows.push(Row {
+                path: path.clone(),
+                depth,
+                is_dir,
+                expanded,
+            });
+            if expanded {
+                self.list(&path, depth + 1, rows);
+            }
+        }
+    }
+
+    pub fn move_selection(&mut self, rows: i32) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let last = self.rows.len() as i64 - 1;
+        self.selected = (self.selected as i64 + rows as i64).clamp(0, last) as usize;
+    }
+
+    pub fn select(&mut self, row: usize) {
+        if row < self.rows.len() {
+            self.selected = row;
+        }
+    }
+
+    // returns whether the path has a row
+    pub fn select_path(&mut self, path: &Path) -> bool {
+        match self.rows.iter().position(|row| row.path == path) {
+            Some(row) => {
+                self.selected = row;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // opens up every directory above a path and selects it
+    pub fn reveal(&mut self, path: &Path) {
+        for ancestor in path.ancestors().skip(1) {
+            if !ancestor.as_os_str().is_empty() {
+                self.expanded.insert(ancestor.to_path_buf());
+            }
+        }
+        self.refresh();
+        self.select_path(path);
+    }
+
+    // expands a collapsed directory and collapses an expanded one
+    pub fn toggle(&mut self, row: usize) {
+        let Some(row) = self.rows.get(row).filter(|row| row.is_dir) else {
+            return;
+        };
+        let path = row.path.clone();
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+        self.refresh();
+    }
+
+    // right goes into a directory, opening it first if it's collapsed
+    pub fn expand(&mut self) {
+        match self.selected_row() {
+            Some(row) if row.is_dir && !row.expanded => self.toggle(self.selected),
+            Some(row) if row.is_dir => self.move_selection(1),
+            _ => {}
+        }
+    }
+
+    // left closes a directory, or goes up to the one a row is in
+    pub fn collapse(&mut self) {
+        let Some(row) = self.selected_row() else {
+            return;
+        };
+        if row.is_dir && row.expanded {
+            self.toggle(self.selected);
+        } else if let Some(parent) = row.path.parent().map(Path::to_path_buf) {
+            self.select_path(&parent);
+        }
+    }
+
+    // where a new file goes for the selected row: inside it when it's a
+    // directory, next to it otherwise
+    pub fn selected_dir(&self) -> PathBuf {
+        match self.selected_row() {
+            Some(row) if row.is_dir => row.path.clone(),
+            Some(row) => row.path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => PathBuf::new(),
+        }
+    }
+
+    // a name ending in / makes a directory, missing directories on the way
+    // are made too
+    pub fn create(&mut self, path: &Path, is_dir: bool) -> Result<(), String> {
+        let full_path = self.root.join(path);
+        if full_path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+        let created = match is_dir {
+            true => fs::create_dir_all(&full_path),
+            false => full_path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&full_path, "")),
+        };
+        created.map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+        self.reveal(path);
+        Ok(())
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<(), String> {
+        let full_to = self.root.join(to);
+        if full_to.exists() {
+            return Err(format!("{} already exists", to.display()));
+        }
+        if let Some(parent) = full_to.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::rename(self.root.join(from), &full_to)
+            .map_err(|e| format!("could not rename {}: {}", from.display(), e))?;
+        if self.expanded.remove(from) {
+            self.expanded.insert(to.to_path_buf());
+        }
+        self.reveal(to);
+        Ok(())
+    }
+
+    // directories go with everything in them
+    pub fn delete(&mut self, path: &Path) -> Result<(), String> {
+        let full_path = self.root.join(path);
+        let deleted = match full_path.is_dir() {
+            true => fs::remove_dir_all(&full_path),
+            false => fs::remove_file(&full_path),
+        };
+        deleted.map_err(|e| format!("could not delete {}: {}", path.display(), e))?;
+        self.refresh();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileTree;
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    fn paths(tree: &FileTree) -> Vec<PathBuf> {
+        tree.rows().iter().map(|row| row.path.clone()).collect()
+    }
+
+    #[test]
+    fn browse_and_change() {
+        let root = std::env::temp_dir().join(format!("file_tree_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("b.txt"), "").unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+
+        let mut tree = FileTree::new(root.clone());
+        assert_eq!(paths(&tree), vec![Path::new("src"), Path::new("b.txt")]);
+        tree.expand();
+        assert_eq!(tree.rows()[1].path, Path::new("src/nested"));
+        assert_eq!(tree.rows()[2].depth, 1);
+        tree.move_selection(2);
+        assert_eq!(tree.selected_row().unwrap().path, Path::new("src/main.rs"));
+        assert_eq!(tree.selected_dir(), Path::new("src"));
+        tree.collapse();
+        assert_eq!(tree.selected(), 0);
+        tree.collapse();
+        assert_eq!(paths(&tree), vec![Path::new("src"), Path::new("b.txt")]);
+
+        tree.create(Path::new("src/nested/new.rs"), false).unwrap();
+        assert_eq!(
+            tree.selected_row().unwrap().path,
+            Path::new("src/nested/new.rs")
+        );
+        assert!(tree.create(Path::new("b.txt"), false).is_err());
+        tree.rename(Path::new("src/nested"), Path::new("src/inner"))
+            .unwrap();
+        assert!(root.join("src/inner/new.rs").is_file());
+        assert!(tree.select_path(Path::new("src/inner/new.rs")));
+        tree.delete(Path::new("src/inner")).unwrap();
+        assert!(!root.join("src/inner").exists());
+        assert_eq!(
+            paths(&tree),
+            vec![
+                Path::new("src"),
+                Path::new("src/main.rs"),
+                Path::new("b.txt")
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+// tells which open files something else wrote, through inotify. The
+// directories they're in are watched rather than the files themselves, since
+// git and most tools write a new file and rename it over the old one, which
+// would leave a watch on the file pointing at one that's gone
+pub struct FileWatcher {
+    inotify: Inotify,
+    // each watched directory as the files in it were named
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    files: HashSet<PathBuf>,
+    buffer: Vec<u8>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Result<FileWatcher, String> {
+        let inotify = Inotify::init().map_err(|e| format!("could not watch files: {e}"))?;
+        Ok(FileWatcher {
+            inotify,
+            dirs: HashMap::new(),
+            files: HashSet::new(),
+            buffer: vec![0; 4096],
+        })
+    }
+
+    // starts reporting writes to a file, watching it again does nothing
+    pub fn watch(&mut self, file: &Path) {
+        if self.files.contains(file) {
+            return;
+        }
+        let dir = file.parent().unwrap_or(Path::new(""));
+        let watched = match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir,
+        };
+        // the same directory gives back the descriptor it already has
+        let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO;
+        if let Ok(descriptor) = self.inotify.watches().add(watched, mask) {
+            self.dirs.insert(descriptor, dir.to_path_buf());
+            self.files.insert(file.to_path_buf());
+        }
+    }
+
+    // the watched files written or replaced since the last call, without
+    // waiting. Every one of them when the kernel dropped events
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = HashSet::new();
+        loop {
+            // nothing left to read comes back as an error too
+            let Ok(events) = self.inotify.read_events(&mut self.buffer) else {
+                break;
+            };
+            for event in events {
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    return self.files.iter().cloned().collect();
+                }
+                let (Some(dir), Some(name)) = (self.dirs.get(&event.wd), event.name) else {
+                    continue;
+                };
+                let file = dir.join(name);
+                if self.files.contains(&file) {
+                    changed.insert(file);
+                }
+            }
+        }
+        changed.into_iter().collect()
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{swap, text_buffer::Edit};
+
+const MAGIC: &[u8] = b"text_editor undo 2\n";
+// stands for no redo child in the saved file
+const NONE: u64 = u64::MAX;
+
+// a state the text was in, reached from its parent by making the edits.
+// The edits undo as one step
+pub struct Node {
+    pub parent: usize,
+    pub edits: Vec<Edit>,
+    // seconds since the epoch when the edits were made
+    pub time: u64,
+    // the child redo goes to, the one most recently left or made
+    redo_child: Option<usize>,
+}
+
+// every state a buffer's text has been in. Undoing and then editing starts
+// a new branch instead of throwing away what was undone. Node 0 is the text
+// as it was opened and nodes are numbered in the order they were made, so
+// going through the numbers goes through the states as they happened
+pub struct UndoTree {
+    nodes: Vec<Node>,
+    current: usize,
+}
+
+impl UndoTree {
+    pub fn new() -> UndoTree {
+        UndoTree {
+            nodes: vec![Node {
+                parent: 0,
+                edits: Vec::new(),
+                time: now(),
+                redo_child: None,
+            }],
+            current: 0,
+        }
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    // the node for the text as it is now
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    // edits just made, as a new child of the current node
+    pub fn push(&mut self, edits: Vec<Edit>) {
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            parent: self.current,
+            edits,
+            time: now(),
+            redo_child: None,
+        });
+        self.nodes[self.current].redo_child = Some(node);
+        self.current = node;
+    }
+
+    // moves to the parent and returns the node whose edits are to be undone
+    pub fn undo(&mut self) -> Option<usize> {
+        if self.current == 0 {
+            return None;
+        }
+        let node = self.current;
+        self.current = self.nodes[node].parent;
+        self.nodes[self.current].redo_child = Some(node);
+        Some(node)
+    }
+
+    // moves to the child last left and returns it, its edits are to be made
+    pub fn redo(&mut self) -> Option<usize> {
+        let child = self.nodes[self.current].redo_child?;
+        self.current = child;
+        Some(child)
+    }
+
+    // moves to any node and returns the way there: nodes to undo up to where
+    // both branches meet, then nodes to redo, true for redo
+    pub fn go_to(&mut self, target: usize) -> Vec<(usize, bool)> {
+        let up = self.path_to_root(self.current);
+        let down = self.path_to_root(target);
+        let on_down: HashSet<usize> = down.iter().copied().collect();
+        let meet = *up.iter().find(|node| on_down.contains(node)).unwrap();
+        let mut steps: Vec<(usize, bool)> = up
+            .iter()
+            .take_while(|node| **node != meet)
+            .map(|node| (*node, false))
+            .collect();
+        let down: Vec<usize> = down.into_iter().take_while(|node| *node != meet).collect();
+        for node in down.into_iter().rev() {
+            let parent = self.nodes[node].parent;
+            self.nodes[parent].redo_child = Some(node);
+            steps.push((node, true));
+        }
+        self.current = target;
+        steps
+    }
+
+    // a node and everything above it, ending with the root
+    fn path_to_root(&self, mut node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        while node != 0 {
+            node = self.nodes[node].parent;
+            path.push(node);
+        }
+        path
+    }
+
+    // the tree drawn a row at a time, newest first, like git log --graph.
+    // Each branch gets a column, a node is o, or @ when it's the current
+    // one, and a row with no node joins branches back into their parent's
+    // column
+    pub fn graph(&self) -> Vec<(String, Option<usize>)> {
+        let mut rows = Vec::new();
+        // the node each column is waiting for
+        let mut lanes: Vec<Option<usize>> = Vec::new();
+        for node in (0..self.nodes.len()).rev() {
+            let waiting: Vec<usize> = (0..lanes.len())
+                .filter(|lane| lanes[*lane] == Some(node))
+                .collect();
+            let column = match waiting.first() {
+                Some(column) => *column,
+                None => match lanes.iter().position(Option::is_none) {
+                    Some(free) => free,
+                    None => {
+                        lanes.push(None);
+                        lanes.len() - 1
+                    }
+                },
+            };
+            if waiting.len() > 1 {
+                let mut join = String::new();
+                for (lane, waiting_for) in lanes.iter().enumerate() {
+                    join.push_str(match waiting_for {
+                        _ if lane > column && waiting.contains(&lane) => "/ ",
+                        Some(_) => "| ",
+                        None => "  ",
+                    });
+                }
+                // the slash goes between the columns it joins
+                let join = join.replace(" /", "/ ");
+                rows.push((join.trim_end().to_string(), None));
+                for lane in &waiting[1..] {
+                    lanes[*lane] = None;
+                }
+            }
+            let mut row = String::new();
+            for (lane, waiting_for) in lanes.iter().enumerate() {
+                row.push_str(match waiting_for {
+                    _ if lane == column && node == self.current => "@ ",
+                    _ if lane == column => "o ",
+                    Some(_) => "| ",
+                    None => "  ",
+                });
+            }
+            rows.push((row.trim_end().to_string(), Some(node)));
+            lanes[column] = (node != 0).then(|| self.nodes[node].parent);
+            while lanes.last() == Some(&None) {
+                lanes.pop();
+            }
+        }
+        rows
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+// how long ago a time was, roughly
+pub fn age(time: u64, now: u64) -> String {
+    let seconds = now.saturating_sub(time);
+    match seconds {
+        0..5 => "just now".to_string(),
+        5..60 => format!("{seconds}s ago"),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+// $XDG_CACHE_HOME/text_editor/undo, falling back to ~/.cache
+pub fn default_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_home.join("text_editor").join("undo"))
+}
+
+// keeps a document's history in the cache directory, along with a hash of
+// the text it leads up to so it's only used while the file still matches
+pub fn save(dir: &Path, document: &Path, text: &[u8], tree: &UndoTree) -> Result<(), String> {
+    let (path, document) = cache_path(dir, document)?;
+    let mut data = MAGIC.to_vec();
+    write_bytes(&mut data, document.as_os_str().as_encoded_bytes());
+    data.extend_from_slice(&swap::hash(text).to_le_bytes());
+    data.extend_from_slice(&(tree.current as u64).to_le_bytes());
+    data.extend_from_slice(&(tree.nodes.len() as u64).to_le_bytes());
+    for node in &tree.nodes {
+        let redo_child = node.redo_child.map_or(NONE, |child| child as u64);
+        for number in [node.parent as u64, node.time, redo_child] {
+            data.extend_from_slice(&number.to_le_bytes());
+        }
+        data.extend_from_slice(&(node.edits.len() as u64).to_le_bytes());
+        for edit in &node.edits {
+            let (tag, offset, text) = match edit {
+                Edit::Insert { offset, text } => (b'i', offset, text),
+                Edit::Delete { offset, text } => (b'd', offset, text),
+            };
+            data.push(tag);
+            data.extend_from_slice(&(*offset as u64).to_le_bytes());
+            write_bytes(&mut data, text);
+        }
+    }
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
+
+// the history saved for a document, as long as it was saved with the text
+// the document has now
+pub fn load(dir: &Path, document: &Path, text: &[u8]) -> Option<UndoTree> {
+    let (path, document) = cache_path(dir, document).ok()?;
+    let data = fs::read(path).ok()?;
+    let mut reader = Reader {
+        data: data.strip_prefix(MAGIC)?,
+    };
+    // different paths can share a file name when their hashes collide
+    if reader.bytes()? != document.as_os_str().as_encoded_bytes()
+        || reader.number()? != swap::hash(text)
+    {
+        return None;
+    }
+    let current = reader.number()? as usize;
+    let count = reader.number()? as usize;
+    let mut nodes = Vec::new();
+    for index in 0..count {
+        let parent = reader.number()? as usize;
+        let time = reader.number()?;
+        let redo_child = match reader.number()? {
+            NONE => None,
+            child => Some(child as usize),
+        };
+        // a parent always comes before its children
+        if (index > 0 && parent >= index) || redo_child.is_some_and(|child| child >= count) {
+            return None;
+        }
+        let edits = (0..reader.number()?)
+            .map(|_| {
+                let tag = reader.byte()?;
+                let offset = reader.number()? as usize;
+                let text = reader.bytes()?.to_vec();
+                match tag {
+                    b'i' => Some(Edit::Insert { offset, text }),
+                    b'd' => Some(Edit::Delete { offset, text }),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<Edit>>>()?;
+        nodes.push(Node {
+            parent,
+            edits,
+            time,
+            redo_child,
+        });
+    }
+    if current >= nodes.len() {
+        return None;
+    }
+    Some(UndoTree { nodes, current })
+}
+
+// the file is named after a hash of the document's full path
+fn cache_path(dir: &Path, document: &Path) -> Result<(PathBuf, PathBuf), String> {
+    let document = std::path::absolute(document).map_err(|e| e.to_string())?;
+    let hash = swap::hash(document.as_os_str().as_encoded_bytes());
+    Ok((dir.join(format!("{hash:016x}")), document))
+}
+
+fn write_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    data.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.number()? as usize;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{age, UndoTree};
+    use crate::text_buffer::Edit;
+
+    fn edit(text: &str) -> Vec<Edit> {
+        vec![Edit::Insert {
+            offset: 0,
+            text: text.as_bytes().to_vec(),
+        }]
+    }
+
+    #[test]
+    fn branches() {
+        let mut tree = UndoTree::new();
+        tree.push(edit("a"));
+        tree.push(edit("b"));
+        assert_eq!(tree.undo(), Some(2));
+        tree.push(edit("c"));
+        assert_eq!(tree.redo(), None);
+        assert_eq!(tree.go_to(2), vec![(3, false), (2, true)]);
+        assert_eq!(tree.undo(), Some(2));
+        // redo follows the branch last left
+        assert_eq!(tree.redo(), Some(2));
+        assert_eq!(tree.go_to(0), vec![(2, false), (1, false)]);
+        assert_eq!(tree.undo(), None);
+        assert_eq!(tree.redo(), Some(1));
+        assert_eq!(tree.redo(), Some(2));
+
+        let graph = tree.graph();
+        let graph: Vec<(&str, Option<usize>)> = graph
+            .iter()
+            .map(|(lanes, node)| (lanes.as_str(), *node))
+            .collect();
+        assert_eq!(
+            graph,
+            vec![
+                ("o", Some(3)),
+                ("| @", Some(2)),
+                ("|/", None),
+                ("o", Some(1)),
+                ("o", Some(0)),
+            ]
+        );
+        assert_eq!(age(100, 102), "just now");
+        assert_eq!(age(0, 7200), "2h ago");
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use crate::config::Preset;
+
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub super_key: bool,
+}
+
+// a key pressed with some modifiers held, keys are named the way SDL names
+// them but lowercased with spaces removed, e.g. "s", "left", "pagedown", "["
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Chord {
+    pub key: String,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyPress {
+    Command(String),
+    // part of a longer sequence, waiting for the next chord
+    Pending,
+    Unbound,
+}
+
+pub struct Keymap {
+    bindings: HashMap<Vec<Chord>, String>,
+    pending: Vec<Chord>,
+}
+
+const DEFAULT_BINDINGS: [(&str, &str); 44] = [
+    ("ctrl+s", "save"),
+    ("ctrl+[", "redo"),
+    ("ctrl+]", "undo"),
+    ("ctrl+alt+]", "undo_older"),
+    ("ctrl+alt+[", "undo_newer"),
+    ("ctrl+u", "undo_tree"),
+    ("backspace", "delete_backward"),
+    ("tab", "indent"),
+    ("return", "newline"),
+    ("left", "cursor_left"),
+    ("right", "cursor_right"),
+    ("up", "cursor_up"),
+    ("down", "cursor_down"),
+    ("ctrl+left", "word_left"),
+    ("ctrl+right", "word_right"),
+    ("home", "smart_home"),
+    ("end", "line_end"),
+    ("ctrl+up", "paragraph_up"),
+    ("ctrl+down", "paragraph_down"),
+    ("ctrl+backspace", "delete_word_backward"),
+    ("ctrl+delete", "delete_word_forward"),
+    ("ctrl+d", "add_next_occurrence"),
+    ("alt+shift+left", "block_left"),
+    ("alt+shift+right", "block_right"),
+    ("alt+shift+up", "add_cursor_above"),
+    ("alt+shift+down", "add_cursor_below"),
+    ("escape", "single_cursor"),
+    ("ctrl+tab", "next_buffer"),
+    ("ctrl+shift+tab", "previous_buffer"),
+    ("ctrl+w", "close_buffer"),
+    ("ctrl+o", "open_file"),
+    ("ctrl+p", "find_file"),
+    ("ctrl+b", "toggle_file_tree"),
+    ("ctrl+shift+e", "focus_file_tree"),
+    ("ctrl+\\", "split_right"),
+    ("ctrl+shift+\\", "split_down"),
+    ("ctrl+shift+w", "close_pane"),
+    ("alt+left", "focus_pane_left"),
+    ("alt+right", "focus_pane_right"),
+    ("alt+up", "focus_pane_up"),
+    ("alt+down", "focus_pane_down"),
+    ("ctrl+g", "goto_line"),
+    ("ctrl+shift+p", "command_palette"),
+    ("ctrl+q", "quit"),
+];
+
+// layered over the defaults when the emacs keymap is chosen
+const EMACS_BINDINGS: [(&str, &str); 26] = [
+    ("ctrl+a", "line_start"),
+    ("ctrl+e", "line_end"),
+    ("ctrl+f", "cursor_right"),
+    ("ctrl+b", "cursor_left"),
+    ("ctrl+n", "cursor_down"),
+    ("ctrl+p", "cursor_up"),
+    ("ctrl+k", "kill_line"),
+    ("ctrl+w", "kill_region"),
+    ("alt+w", "copy_region"),
+    ("ctrl+y", "yank"),
+    ("alt+y", "yank_pop"),
+    ("ctrl+space", "set_mark"),
+    ("ctrl+g", "cancel"),
+    ("alt+g g", "goto_line"),
+    ("ctrl+x ctrl+s", "save"),
+    ("ctrl+x ctrl+c", "quit"),
+    ("ctrl+x ctrl+f", "open_file"),
+    ("ctrl+x k", "close_buffer"),
+    ("ctrl+x u", "undo_tree"),
+    ("ctrl+x d", "toggle_file_tree"),
+    ("ctrl+x right", "next_buffer"),
+    ("ctrl+x left", "previous_buffer"),
+    ("ctrl+x 2", "split_down"),
+    ("ctrl+x 3", "split_right"),
+    ("ctrl+x 0", "close_pane"),
+    ("ctrl+x o", "focus_next_pane"),
+];
+
+impl Chord {
+    pub fn new(key: &str, modifiers: Modifiers) -> Chord {
+        Chord {
+            key: normalise_key(key),
+            modifiers,
+        }
+    }
+
+    // "ctrl+shift+p", the key goes last so "ctrl++" is ctrl and plus
+    pub fn parse(text: &str) -> Result<Chord, String> {
+        let (modifier_names, key) = match text.strip_suffix("++") {
+            Some(modifier_names) => (modifier_names, "+"),
+            None => match text.rsplit_once('+') {
+                Some((modifier_names, key)) => (modifier_names, key),
+                None => ("", text),
+            },
+        };
+        if key.is_empty() {
+            return Err(format!("'{text}' is missing a key"));
+        }
+        let mut modifiers = Modifiers::default();
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            match name.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" | "meta" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "super" | "cmd" | "win" => modifiers.super_key = true,
+                _ => return Err(format!("unknown modifier '{name}' in '{text}'")),
+            }
+        }
+        Ok(Chord::new(key, modifiers))
+    }
+
+    pub fn is_modifier(&self) -> bool {
+        [
+            "leftctrl",
+            "rightctrl",
+            "leftshift",
+            "rightshift",
+            "leftalt",
+            "rightalt",
+            "leftgui",
+            "rightgui",
+        ]
+        .contains(&self.key.as_str())
+    }
+}
+
+// a sequence of chords separated by spaces, "ctrl+k ctrl+c"
+pub fn parse_sequence(text: &str) -> Result<Vec<Chord>, String> {
+    let sequence = text
+        .split_whitespace()
+        .map(Chord::parse)
+        .collect::<Result<Vec<Chord>, String>>()?;
+    if sequence.is_empty() {
+        return Err("empty key binding".to_string());
+    }
+    Ok(sequence)
+}
+
+fn normalise_key(key: &str) -> String {
+    let key: String = key.to_lowercase().split_whitespace().collect();
+    match key.as_str() {
+        "enter" => "return".to_string(),
+        "esc" => "escape".to_string(),
+        "del" => "delete".to_string(),
+        "plus" => "+".to_string(),
+        "minus" => "-".to_string(),
+        _ => key,
+    }
+}
+
+impl Keymap {
+    pub fn new(preset: Preset) -> Keymap {
+        let mut keymap = Keymap {
+            bindings: HashMap::new(),
+            pending: Vec::new(),
+        };
+        let preset_bindings: &[(&str, &str)] = match preset {
+            Preset::Emacs => &EMACS_BINDINGS,
+            Preset::Default | Preset::Vim => &[],
+        };
+        for (keys, command) in DEFAULT_BINDINGS.iter().chain(preset_bindings) {
+            keymap.bind(parse_sequence(keys).unwrap(), command);
+        }
+        keymap
+    }
+
+    // layers the config's bindings over the preset's, binding to "none"
+    // removes one. Errors carry the line of the bad binding
+    pub fn with_bindings(
+        preset: Preset,
+        bindings: &[(String, String, usize)],
+        commands: &[&str],
+    ) -> Result<Keymap, String> {
+        let mut keymap = Keymap::new(preset);
+        for (keys, command, line_num) in bindings {
+            let sequence = parse_sequence(keys).map_err(|e| format!("{line_num}: {e}"))?;
+            if command == "none" {
+                keymap.bindings.remove(&sequence);
+            } else if commands.contains(&command.as_str()) {
+                keymap.bind(sequence, command);
+            } else {
+                return Err(format!("{line_num}: unknown command '{command}'"));
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn bind(&mut self, sequence: Vec<Chord>, command: &str) {
+        self.bindings.insert(sequence, command.to_string());
+    }
+
+    pub fn press(&mut self, chord: Chord) -> KeyPress {
+        self.pending.push(chord);
+        if let Some(command) = self.bindings.get(&self.pending) {
+            self.pending.clear();
+            return KeyPress::Command(command.clone());
+        }
+        if self
+            .bindings
+            .keys()
+            .any(|sequence| sequence.starts_with(&self.pending))
+        {
+            return KeyPress::Pending;
+        }
+        self.pending.clear();
+        KeyPress::Unbound
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_sequence, Chord, KeyPress, Keymap, Modifiers};
+    use crate::config::Preset;
+
+    fn ctrl(key: &str) -> Chord {
+        Chord::new(
+            key,
+            Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            },
+        )
+    }
+
+    #[test]
+    fn parse_chords() {
+        assert_eq!(Chord::parse("ctrl+S"), Ok(ctrl("s")));
+        assert_eq!(Chord::parse("ctrl++"), Ok(ctrl("+")));
+        assert_eq!(
+            Chord::parse("Ctrl+Shift+Page Down").map(|chord| chord.key),
+            Ok("pagedown".to_string())
+        );
+        assert_eq!(
+            Chord::parse("alt+super+enter"),
+            Ok(Chord::new(
+                "return",
+                Modifiers {
+                    alt: true,
+                    super_key: true,
+                    ..Modifiers::default()
+                }
+            ))
+        );
+        assert!(Chord::parse("hyper+x").is_err());
+        assert!(Chord::parse("ctrl+").is_err());
+        assert_eq!(
+            parse_sequence("ctrl+k  ctrl+c"),
+            Ok(vec![ctrl("k"), ctrl("c")])
+        );
+    }
+
+    #[test]
+    fn sequences() {
+        let mut keymap = Keymap::with_bindings(
+            Preset::Default,
+            &[
+                ("ctrl+k ctrl+c".to_string(), "save".to_string(), 1),
+                ("ctrl+s".to_string(), "none".to_string(), 2),
+            ],
+            &["save"],
+        )
+        .unwrap();
+        assert_eq!(keymap.press(ctrl("s")), KeyPress::Unbound);
+        assert_eq!(keymap.press(ctrl("k")), KeyPress::Pending);
+        assert_eq!(
+            keymap.press(ctrl("c")),
+            KeyPress::Command("save".to_string())
+        );
+        assert_eq!(keymap.press(ctrl("k")), KeyPress::Pending);
+        assert_eq!(keymap.press(ctrl("x")), KeyPress::Unbound);
+        assert_eq!(
+            keymap.press(Chord::new("Left", Modifiers::default())),
+            KeyPress::Command("cursor_left".to_string())
+        );
+    }
+
+    #[test]
+    fn emacs_preset() {
+        let mut keymap = Keymap::new(Preset::Emacs);
+        assert_eq!(keymap.press(ctrl("x")), KeyPress::Pending);
+        assert_eq!(
+            keymap.press(ctrl("s")),
+            KeyPress::Command("save".to_string())
+        );
+        assert_eq!(
+            keymap.press(ctrl("k")),
+            KeyPress::Command("kill_line".to_string())
+        );
+        assert_eq!(
+            keymap.press(ctrl("g")),
+            KeyPress::Command("cancel".to_string())
+        );
+        assert_eq!(
+            Keymap::new(Preset::Default).press(ctrl("g")),
+            KeyPress::Command("goto_line".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_bindings() {
+        assert_eq!(
+            Keymap::with_bindings(
+                Preset::Default,
+                &[("ctrl+s".to_string(), "sav".to_string(), 7)],
+                &["save"]
+            )
+            .err(),
+            Some("7: unknown command 'sav'".to_string())
+        );
+        assert_eq!(
+            Keymap::with_bindings(
+                Preset::Default,
+                &[("ctl+s".to_string(), "save".to_string(), 3)],
+                &["save"]
+            )
+            .err(),
+            Some("3: unknown modifier 'ctl' in 'ctl+s'".to_string())
+        );
+    }
+}
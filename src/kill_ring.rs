@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+const KILL_RING_SIZE: usize = 60;
+
+// text that was cut, newest first. Yanking takes the newest and yanking again
+// straight after steps back through the older ones
+pub struct KillRing {
+    entries: VecDeque<String>,
+    yank_index: usize,
+}
+
+impl KillRing {
+    pub fn new() -> KillRing {
+        KillRing {
+            entries: VecDeque::new(),
+            yank_index: 0,
+        }
+    }
+
+    // append joins the text onto the newest entry, used when kills happen one
+    // after another so they yank back as one
+    pub fn kill(&mut self, text: &str, append: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.entries.front_mut() {
+            Some(newest) if append => newest.push_str(text),
+            _ => {
+                self.entries.push_front(text.to_string());
+                self.entries.truncate(KILL_RING_SIZE);
+            }
+        }
+        self.yank_index = 0;
+    }
+
+    pub fn yank(&mut self) -> Option<&str> {
+        self.yank_index = 0;
+        self.entries.front().map(|entry| entry.as_str())
+    }
+
+    // the entry before the one yanked last, wrapping round to the newest
+    pub fn yank_pop(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.yank_index = (self.yank_index + 1) % self.entries.len();
+        Some(&self.entries[self.yank_index])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KillRing;
+
+    #[test]
+    fn kill_and_yank() {
+        let mut ring = KillRing::new();
+        assert_eq!(ring.yank(), None);
+        assert_eq!(ring.yank_pop(), None);
+        ring.kill("one", false);
+        ring.kill("two", false);
+        ring.kill(" more", true);
+        ring.kill("", false);
+        assert_eq!(ring.yank(), Some("two more"));
+        assert_eq!(ring.yank_pop(), Some("one"));
+        assert_eq!(ring.yank_pop(), Some("two more"));
+        ring.kill("three", false);
+        assert_eq!(ring.yank_pop(), Some("two more"));
+        assert_eq!(ring.yank(), Some("three"));
+    }
+}
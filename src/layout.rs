@@ -0,0 +1,319 @@
+// how the text area is shared out between panes. Panes are numbered by
+// their index in the screen's list and every split halves the space it has
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Side {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// a rectangle in logical pixels
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Bounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Bounds {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.x <= x
+            && x < self.x + self.width as i32
+            && self.y <= y
+            && y < self.y + self.height as i32
+    }
+
+    fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+}
+
+enum Node {
+    Pane(usize),
+    // side_by_side splits left and right, otherwise top and bottom
+    Split {
+        side_by_side: bool,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+pub struct Layout {
+    root: Node,
+}
+
+impl Layout {
+    pub fn new() -> Layout {
+        Layout {
+            root: Node::Pane(0),
+        }
+    }
+
+    pub fn pane_count(&self) -> usize {
+        Self::count(&self.root)
+    }
+
+    fn count(node: &Node) -> usize {
+        match node {
+            Node::Pane(_) => 1,
+            Node::Split { first, second, .. } => Self::count(first) + Self::count(second),
+        }
+    }
+
+    // halves a pane, the new one goes on the given side of it
+    pub fn split(&mut self, pane: usize, side: Side, new_pane: usize) {
+        Self::split_node(&mut self.root, pane, side, new_pane);
+    }
+
+    fn split_node(node: &mut Node, pane: usize, side: Side, new_pane: usize) -> bool {
+        match node {
+            Node::Pane(index) if *index == pane => {
+                let (first, second) = match side {
+                    Side::Left | Side::Up => (new_pane, pane),
+                    Side::Right | Side::Down => (pane, new_pane),
+                };
+                *node = Node::Split {
+                    side_by_side: matches!(side, Side::Left | Side::Right),
+                    first: Box::new(Node::Pane(first)),
+                    second: Box::new(Node::Pane(second)),
+                };
+                true
+            }
+            Node::Pane(_) => false,
+            Node::Split { first, second, .. } => {
+                Self::split_node(first, pane, side, new_pane)
+                    || Self::split_node(second, pane, side, new_pane)
+            }
+        }
+    }
+
+    // the pane's neighbour takes over its space and panes after it move down
+    // an index, matching them being removed from a list. The last pane stays
+    pub fn remove(&mut self, pane: usize) -> bool {
+        if !Self::remove_node(&mut self.root, pane) {
+            return false;
+        }
+        Self::renumber(&mut self.root, pane);
+        true
+    }
+
+    fn remove_node(node: &mut Node, pane: usize) -> bool {
+        let Node::Split { first, second, .. } = node else {
+            return false;
+        };
+        let keep = match (first.as_ref(), second.as_ref()) {
+            (Node::Pane(index), _) if *index == pane => {
+                std::mem::replace(second.as_mut(), Node::Pane(0))
+            }
+            (_, Node::Pane(index)) if *index == pane => {
+                std::mem::replace(first.as_mut(), Node::Pane(0))
+            }
+            _ => return Self::remove_node(first, pane) || Self::remove_node(second, pane),
+        };
+        *node = keep;
+        true
+    }
+
+    fn renumber(node: &mut Node, removed: usize) {
+        match node {
+            Node::Pane(index) if *index > removed => *index -= 1,
+            Node::Pane(_) => {}
+            Node::Split { first, second, .. } => {
+                Self::renumber(first, removed);
+                Self::renumber(second, removed);
+            }
+        }
+    }
+
+    // where every pane goes in the area, indexed by pane. Splits leave a gap
+    // divider wide between their halves
+    pub fn bounds(&self, area: Bounds, divider: u32) -> Vec<Bounds> {
+        let mut bounds = vec![area; self.pane_count()];
+        Self::place(&self.root, area, divider, &mut bounds, &mut Vec::new());
+        bounds
+    }
+
+    // the gaps between panes
+    pub fn dividers(&self, area: Bounds, divider: u32) -> Vec<Bounds> {
+        let mut dividers = Vec::new();
+        let mut bounds = vec![area; self.pane_count()];
+        Self::place(&self.root, area, divider, &mut bounds, &mut dividers);
+        dividers
+    }
+
+    fn place(
+        node: &Node,
+        area: Bounds,
+        divider: u32,
+        bounds: &mut Vec<Bounds>,
+        dividers: &mut Vec<Bounds>,
+    ) {
+        match node {
+            Node::Pane(index) => bounds[*index] = area,
+            Node::Split {
+                side_by_side,
+                first,
+                second,
+            } => {
+                let (first_area, gap, second_area) = match side_by_side {
+                    true => {
+                        let width = area.width.saturating_sub(divider) / 2;
+                        let gap_x = area.x + width as i32;
+                        let second_x = gap_x + divider as i32;
+                        (
+                            Bounds { width, ..area },
+                            Bounds {
+                                x: gap_x,
+                                width: divider.min(area.width),
+                                ..area
+                            },
+                            Bounds {
+                                x: second_x,
+                                width: (area.right() - second_x).max(0) as u32,
+                                ..area
+                            },
+                        )
+                    }
+                    false => {
+                        let height = area.height.saturating_sub(divider) / 2;
+                        let gap_y = area.y + height as i32;
+                        let second_y = gap_y + divider as i32;
+                        (
+                            Bounds { height, ..area },
+                            Bounds {
+                                y: gap_y,
+                                height: divider.min(area.height),
+                                ..area
+                            },
+                            Bounds {
+                                y: second_y,
+                                height: (area.bottom() - second_y).max(0) as u32,
+                                ..area
+                            },
+                        )
+                    }
+                };
+                dividers.push(gap);
+                Self::place(first, first_area, divider, bounds, dividers);
+                Self::place(second, second_area, divider, bounds, dividers);
+            }
+        }
+    }
+}
+
+// the closest pane on the given side of one, among those that overlap it
+// along the other axis
+pub fn neighbour(bounds: &[Bounds], from: usize, side: Side) -> Option<usize> {
+    let start = bounds[from];
+    bounds
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != from)
+        .filter_map(|(index, other)| {
+            let overlaps_rows = other.y < start.bottom() && start.y < other.bottom();
+            let overlaps_columns = other.x < start.right() && start.x < other.right();
+            let distance = match side {
+                Side::Left if overlaps_rows && other.right() <= start.x => start.x - other.right(),
+                Side::Right if overlaps_rows && other.x >= start.right() => other.x - start.right(),
+                Side::Up if overlaps_columns && other.bottom() <= start.y => {
+                    start.y - other.bottom()
+                }
+                Side::Down if overlaps_columns && other.y >= start.bottom() => {
+                    other.y - start.bottom()
+                }
+                _ => return None,
+            };
+            Some((distance, index))
+        })
+        .min()
+        .map(|(_, index)| index)
+}
+
+// the first line a pane height pixels tall should show so line is in view,
+// scrolling from top_line as little as possible
+pub fn scroll_into_view(top_line: u32, line: u32, height: u32, line_height: u32) -> u32 {
+    let visible_rows = (height / line_height.max(1)).max(1);
+    if line < top_line {
+        line
+    } else if line >= top_line + visible_rows {
+        line + 1 - visible_rows
+    } else {
+        top_line
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{neighbour, scroll_into_view, Bounds, Layout, Side};
+
+    const AREA: Bounds = Bounds {
+        x: 0,
+        y: 10,
+        width: 201,
+        height: 101,
+    };
+
+    #[test]
+    fn split_and_remove() {
+        let mut layout = Layout::new();
+        assert_eq!(layout.bounds(AREA, 1), vec![AREA]);
+        layout.split(0, Side::Right, 1);
+        layout.split(1, Side::Down, 2);
+        let bounds = layout.bounds(AREA, 1);
+        assert_eq!(
+            bounds,
+            vec![
+                Bounds {
+                    x: 0,
+                    y: 10,
+                    width: 100,
+                    height: 101
+                },
+                Bounds {
+                    x: 101,
+                    y: 10,
+                    width: 100,
+                    height: 50
+                },
+                Bounds {
+                    x: 101,
+                    y: 61,
+                    width: 100,
+                    height: 50
+                },
+            ]
+        );
+        assert_eq!(layout.dividers(AREA, 1).len(), 2);
+        assert_eq!(neighbour(&bounds, 0, Side::Right), Some(1));
+        assert_eq!(neighbour(&bounds, 2, Side::Left), Some(0));
+        assert_eq!(neighbour(&bounds, 2, Side::Up), Some(1));
+        assert_eq!(neighbour(&bounds, 0, Side::Up), None);
+
+        assert!(layout.remove(1));
+        assert_eq!(layout.pane_count(), 2);
+        assert_eq!(layout.bounds(AREA, 1)[1].height, 101);
+        assert!(layout.remove(0));
+        assert_eq!(layout.bounds(AREA, 1), vec![AREA]);
+        assert!(!layout.remove(0));
+    }
+
+    #[test]
+    fn scrolling() {
+        assert_eq!(scroll_into_view(10, 12, 100, 20), 10);
+        assert_eq!(scroll_into_view(10, 4, 100, 20), 4);
+        assert_eq!(scroll_into_view(10, 20, 100, 20), 16);
+        // opening at a far line before the font is loaded counts 1px rows,
+        // the real line height has to scroll it into view again
+        let top_line = scroll_into_view(0, 499, 580, 1);
+        assert_eq!(top_line, 0);
+        let top_line = scroll_into_view(top_line, 499, 580, 20);
+        assert!(top_line <= 499 && 499 < top_line + 580 / 20);
+    }
+}
@@ -1,5 +1,23 @@
 pub mod atlas;
+pub mod cli;
+mod commands;
+mod config;
+mod cursors;
+mod diff;
 mod doubly_linked_list;
 pub mod editor;
+mod file_finder;
+mod file_tree;
+mod file_watcher;
+mod history;
+mod keymap;
+mod kill_ring;
+mod layout;
+mod markdown;
+mod motion;
+mod palette;
 mod screen;
+mod swap;
 mod text_buffer;
+mod vim;
+mod wrap;
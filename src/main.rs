@@ -1,21 +1,32 @@
-use std::env;
+use std::{env, process};
 
-use text_editor::editor::{Dimensions, Editor};
-const WIDTH: u32 = 800;
-const HEIGHT: u32 = 600;
+use text_editor::{
+    cli::{self, Command},
+    editor::Editor,
+};
 
-pub fn main() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        panic!("Usage: cargo run <filepath>")
+pub fn main() {
+    let command = match cli::parse(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    let args = match command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Command::Version => {
+            println!("text_editor {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Command::Run(args) => args,
+    };
+
+    if let Err(e) = Editor::new(&args).and_then(|mut editor| editor.start()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
     }
-    let mut editor = Editor::new(
-        Dimensions {
-            height: HEIGHT,
-            width: WIDTH,
-        },
-        args[1].clone(),
-    )?;
-    let _ = editor.start();
-    Ok(())
 }
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+// a span of columns [start, end) on a line and how it's emphasised
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Emphasis {
+    pub start: usize,
+    pub end: usize,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "md" || extension == "markdown")
+}
+
+// headings and *emphasis*, **strong** and ***both*** on a single line, the
+// markers included. Nothing carries over from one line to the next so a
+// line can be styled without looking at the rest of the document
+pub fn emphasis(line: &str) -> Vec<Emphasis> {
+    let chars: Vec<char> = line.chars().collect();
+    let hashes = chars.iter().take_while(|c| **c == '#').count();
+    let heading = (1..=6).contains(&hashes) && chars.get(hashes).is_none_or(|c| *c == ' ');
+    let mut spans = Vec::new();
+    if heading {
+        spans.push(Emphasis {
+            start: 0,
+            end: chars.len(),
+            bold: true,
+            italic: false,
+        });
+    }
+    let mut column = 0;
+    while column < chars.len() {
+        let c = chars[column];
+        let run = run_len(&chars, column);
+        // nothing inside code is emphasis
+        if c == '`' {
+            column = find_run(&chars, column + run, '`', run).map_or(chars.len(), |end| end + run);
+            continue;
+        }
+        if (c != '*' && c != '_') || run > 3 || !can_open(&chars, column, run) {
+            column += run;
+            continue;
+        }
+        match find_closing(&chars, column + run, c, run) {
+            Some(close) => {
+                spans.push(Emphasis {
+                    start: column,
+                    end: close + run,
+                    bold: heading || run >= 2,
+                    italic: run != 2,
+                });
+                column = close + run;
+            }
+            None => column += run,
+        }
+    }
+    spans
+}
+
+// how many of the same character start at column
+fn run_len(chars: &[char], column: usize) -> usize {
+    chars[column..]
+        .iter()
+        .take_while(|c| **c == chars[column])
+        .count()
+}
+
+// the next run of exactly len of the character from column on
+fn find_run(chars: &[char], mut column: usize, c: char, len: usize) -> Option<usize> {
+    while column < chars.len() {
+        let run = run_len(chars, column);
+        if chars[column] == c && run == len {
+            return Some(column);
+        }
+        column += run;
+    }
+    None
+}
+
+// a closing marker has to follow something other than a space, an
+// underscore can't close inside a word
+fn find_closing(chars: &[char], from: usize, c: char, len: usize) -> Option<usize> {
+    let mut column = from;
+    while let Some(close) = find_run(chars, column, c, len) {
+        let after = chars.get(close + len);
+        if close > from
+            && !chars[close - 1].is_whitespace()
+            && (c != '_' || after.is_none_or(|c| !c.is_alphanumeric()))
+        {
+            return Some(close);
+        }
+        column = close + len;
+    }
+    None
+}
+
+// an opening marker has to be followed by something other than a space, an
+// underscore can't open inside a word
+fn can_open(chars: &[char], column: usize, len: usize) -> bool {
+    let next = chars.get(column + len);
+    let previous = column.checked_sub(1).map(|i| chars[i]);
+    next.is_some_and(|c| !c.is_whitespace())
+        && (chars[column] != '_' || previous.is_none_or(|c| !c.is_alphanumeric()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::emphasis;
+
+    fn spans(line: &str) -> Vec<(usize, usize, bool, bool)> {
+        emphasis(line)
+            .into_iter()
+            .map(|emphasis| (emphasis.start, emphasis.end, emphasis.bold, emphasis.italic))
+            .collect()
+    }
+
+    #[test]
+    fn styles() {
+        assert_eq!(spans("plain text"), vec![]);
+        assert_eq!(spans("a *b* c"), vec![(2, 5, false, true)]);
+        assert_eq!(
+            spans("**b** _i_"),
+            vec![(0, 5, true, false), (6, 9, false, true)]
+        );
+        assert_eq!(spans("***both***"), vec![(0, 10, true, true)]);
+        assert_eq!(
+            spans("## Title *x*"),
+            vec![(0, 12, true, false), (9, 12, true, true)]
+        );
+        assert_eq!(spans("#hashtag"), vec![]);
+        // not emphasis
+        assert_eq!(spans("a * b * c"), vec![]);
+        assert_eq!(spans("snake_case_name"), vec![]);
+        assert_eq!(spans("`*code*` *x*"), vec![(9, 12, false, true)]);
+        assert_eq!(spans("**open"), vec![]);
+        assert_eq!(spans("é *ü*"), vec![(2, 5, false, true)]);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::text_buffer::Buffer;
+
+// cursor movements bigger than a character, positions are (line, column).
+// word_chars are the characters that count as part of a word on top of
+// letters and digits
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+pub fn class(c: char, word_chars: &str) -> Class {
+    if c.is_whitespace() {
+        Class::Space
+    } else if c.is_alphanumeric() || word_chars.contains(c) {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+fn line_chars(buffer: &Buffer, line: u32) -> Vec<char> {
+    buffer
+        .get_lines(line, line + 1)
+        .pop()
+        .unwrap_or_default()
+        .chars()
+        .collect()
+}
+
+// to the end of the next word, the end of a line steps onto the next one
+pub fn word_right(buffer: &Buffer, (line, column): (u32, u32), word_chars: &str) -> (u32, u32) {
+    let chars = line_chars(buffer, line);
+    let mut column = column as usize;
+    if column >= chars.len() {
+        return match line + 1 < buffer.line_count() {
+            true => (line + 1, 0),
+            false => (line, chars.len() as u32),
+        };
+    }
+    while column < chars.len() && class(chars[column], word_chars) == Class::Space {
+        column += 1;
+    }
+    if let Some(&c) = chars.get(column) {
+        let run = class(c, word_chars);
+        while column < chars.len() && class(chars[column], word_chars) == run {
+            column += 1;
+        }
+    }
+    (line, column as u32)
+}
+
+// to the start of the previous word, the start of a line steps onto the end
+// of the one before
+pub fn word_left(buffer: &Buffer, (line, column): (u32, u32), word_chars: &str) -> (u32, u32) {
+    if column == 0 {
+        return match line {
+            0 => (0, 0),
+            _ => (line - 1, buffer.line_len(line - 1)),
+        };
+    }
+    let chars = line_chars(buffer, line);
+    let mut column = (column as usize).min(chars.len());
+    while column > 0 && class(chars[column - 1], word_chars) == Class::Space {
+        column -= 1;
+    }
+    if column > 0 {
+        let run = class(chars[column - 1], word_chars);
+        while column > 0 && class(chars[column - 1], word_chars) == run {
+            column -= 1;
+        }
+    }
+    (line, column as u32)
+}
+
+// the word the position is in or just after, None when it touches no word
+pub fn word_at(
+    buffer: &Buffer,
+    (line, column): (u32, u32),
+    word_chars: &str,
+) -> Option<((u32, u32), (u32, u32))> {
+    let chars = line_chars(buffer, line);
+    let is_word = |index: usize| class(chars[index], word_chars) == Class::Word;
+    let column = (column as usize).min(chars.len());
+    let mut start = match column < chars.len() && is_word(column) {
+        true => column,
+        false if column > 0 && is_word(column - 1) => column - 1,
+        false => return None,
+    };
+    let mut end = start;
+    while start > 0 && is_word(start - 1) {
+        start -= 1;
+    }
+    while end < chars.len() && is_word(end) {
+        end += 1;
+    }
+    Some(((line, start as u32), (line, end as u32)))
+}
+
+// the first non-whitespace character, or the very start when already there
+pub fn smart_home(buffer: &Buffer, (line, column): (u32, u32)) -> (u32, u32) {
+    let indent = line_chars(buffer, line)
+        .iter()
+        .take_while(|c| c.is_whitespace())
+        .count() as u32;
+    match column == indent {
+        true => (line, 0),
+        false => (line, indent),
+    }
+}
+
+fn is_blank(buffer: &Buffer, line: u32) -> bool {
+    line_chars(buffer, line).iter().all(|c| c.is_whitespace())
+}
+
+// the next blank line after some text, or the end of the buffer
+pub fn paragraph_down(buffer: &Buffer, (line, _): (u32, u32)) -> (u32, u32) {
+    let last_line = buffer.line_count() - 1;
+    let mut line = line;
+    while line < last_line && is_blank(buffer, line) {
+        line += 1;
+    }
+    while line < last_line && !is_blank(buffer, line) {
+        line += 1;
+    }
+    match is_blank(buffer, line) {
+        true => (line, 0),
+        false => (line, buffer.line_len(line)),
+    }
+}
+
+// the previous blank line before some text, or the start of the buffer
+pub fn paragraph_up(buffer: &Buffer, (line, _): (u32, u32)) -> (u32, u32) {
+    let mut line = line;
+    while line > 0 && is_blank(buffer, line) {
+        line -= 1;
+    }
+    while line > 0 && !is_blank(buffer, line) {
+        line -= 1;
+    }
+    (line, 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{paragraph_down, paragraph_up, smart_home, word_at, word_left, word_right};
+    use crate::text_buffer::Buffer;
+    use std::path::PathBuf;
+
+    fn buffer(text: &str) -> Buffer {
+        Buffer::from_text(PathBuf::from("test.txt"), text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn words() {
+        let buffer = buffer("let snake_case = a-b;\nnext");
+        assert_eq!(word_right(&buffer, (0, 0), "_"), (0, 3));
+        assert_eq!(word_right(&buffer, (0, 3), "_"), (0, 14));
+        assert_eq!(word_right(&buffer, (0, 3), ""), (0, 9));
+        assert_eq!(word_right(&buffer, (0, 14), "_-"), (0, 16));
+        assert_eq!(word_right(&buffer, (0, 16), "_-"), (0, 20));
+        assert_eq!(word_right(&buffer, (0, 21), "_"), (1, 0));
+        assert_eq!(word_right(&buffer, (1, 4), "_"), (1, 4));
+        assert_eq!(word_left(&buffer, (0, 14), "_"), (0, 4));
+        assert_eq!(word_left(&buffer, (0, 14), ""), (0, 10));
+        assert_eq!(word_left(&buffer, (1, 0), "_"), (0, 21));
+        assert_eq!(word_left(&buffer, (0, 0), "_"), (0, 0));
+        assert_eq!(word_at(&buffer, (0, 6), "_"), Some(((0, 4), (0, 14))));
+        assert_eq!(word_at(&buffer, (0, 3), "_"), Some(((0, 0), (0, 3))));
+        assert_eq!(word_at(&buffer, (0, 15), "_"), None);
+    }
+
+    #[test]
+    fn home_and_paragraphs() {
+        let buffer = buffer("    indented\n\none\ntwo\n\n\nthree");
+        assert_eq!(smart_home(&buffer, (0, 8)), (0, 4));
+        assert_eq!(smart_home(&buffer, (0, 4)), (0, 0));
+        assert_eq!(smart_home(&buffer, (0, 0)), (0, 4));
+        assert_eq!(paragraph_down(&buffer, (0, 2)), (1, 0));
+        assert_eq!(paragraph_down(&buffer, (1, 0)), (4, 0));
+        assert_eq!(paragraph_down(&buffer, (4, 0)), (6, 5));
+        assert_eq!(paragraph_up(&buffer, (6, 2)), (5, 0));
+        assert_eq!(paragraph_up(&buffer, (3, 0)), (1, 0));
+        assert_eq!(paragraph_up(&buffer, (1, 0)), (0, 0));
+    }
+}
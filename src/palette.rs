@@ -0,0 +1,200 @@
+// a prompt with a list of items under it that narrows down as the query is
+// typed, the list can be empty when only the typed answer is wanted
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Item {
+    pub label: String,
+    // shown after the label but not matched against
+    pub detail: String,
+}
+
+// an item that matched the query, positions are the matched characters of
+// its label so they can be highlighted
+#[derive(Debug, PartialEq, Clone)]
+pub struct Match {
+    pub item: usize,
+    pub positions: Vec<usize>,
+}
+
+pub struct Palette {
+    prompt: String,
+    query: String,
+    items: Vec<Item>,
+    matches: Vec<Match>,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new(prompt: &str, items: Vec<Item>) -> Palette {
+        let mut palette = Palette {
+            prompt: prompt.to_string(),
+            query: String::new(),
+            items,
+            matches: Vec::new(),
+            selected: 0,
+        };
+        palette.filter();
+        palette
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    // index into matches()
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    // the item that would be picked right now
+    pub fn selected_item(&self) -> Option<usize> {
+        self.matches.get(self.selected).map(|m| m.item)
+    }
+
+    // adds items that turned up after it opened, keeping the same item
+    // selected
+    pub fn extend(&mut self, items: Vec<Item>) {
+        if items.is_empty() {
+            return;
+        }
+        let selected = self.selected_item();
+        self.items.extend(items);
+        self.filter();
+        if let Some(index) =
+            selected.and_then(|item| self.matches.iter().position(|m| m.item == item))
+        {
+            self.selected = index;
+        }
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        self.query.push_str(text);
+        self.filter();
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.filter();
+    }
+
+    // moves the selection by some rows, wrapping around the ends
+    pub fn move_selection(&mut self, rows: i32) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len() as i32;
+        self.selected = (self.selected as i32 + rows).rem_euclid(len) as usize;
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(i32, Match)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(item, candidate)| {
+                let (score, positions) = fuzzy_match(&self.query, &candidate.label)?;
+                Some((score, Match { item, positions }))
+            })
+            .collect();
+        // stable so equally good items keep their order
+        scored.sort_by_key(|(score, _)| -score);
+        self.matches = scored.into_iter().map(|(_, m)| m).collect();
+        self.selected = 0;
+    }
+}
+
+// matches the query's characters in order anywhere in the candidate, ignoring
+// case. Runs of consecutive characters and matches at the start of words score
+// higher, gaps between matched characters cost a little
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i32, Vec<usize>)> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut next = 0;
+    for query_char in query.chars().filter(|c| !c.is_whitespace()) {
+        let offset = candidate[next..]
+            .iter()
+            .position(|c| c.to_lowercase().eq(query_char.to_lowercase()))?;
+        let index = next + offset;
+        let word_start = index == 0 || !candidate[index - 1].is_alphanumeric();
+        if positions.last().is_some_and(|last| last + 1 == index) {
+            score += 5;
+        } else if word_start {
+            score += 3;
+        } else {
+            score -= offset.min(3) as i32;
+        }
+        score += 1;
+        positions.push(index);
+        next = index + 1;
+    }
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fuzzy_match, Item, Palette};
+
+    fn items(labels: &[&str]) -> Vec<Item> {
+        labels
+            .iter()
+            .map(|label| Item {
+                label: label.to_string(),
+                detail: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert_eq!(fuzzy_match("", "save").map(|(_, p)| p), Some(vec![]));
+        assert_eq!(
+            fuzzy_match("gl", "goto_line").map(|(_, p)| p),
+            Some(vec![0, 5])
+        );
+        assert_eq!(
+            fuzzy_match("SAVE", "save").map(|(_, p)| p),
+            Some(vec![0, 1, 2, 3])
+        );
+        assert_eq!(fuzzy_match("ev", "save"), None);
+        assert!(
+            fuzzy_match("cu", "cursor_up").unwrap().0 > fuzzy_match("cu", "clear_up").unwrap().0
+        );
+    }
+
+    #[test]
+    fn filtering() {
+        let mut palette = Palette::new("", items(&["cursor_up", "undo", "redo", "quit"]));
+        assert_eq!(palette.matches().len(), 4);
+        palette.insert("do");
+        let labels: Vec<usize> = palette.matches().iter().map(|m| m.item).collect();
+        assert_eq!(labels, vec![1, 2]);
+        palette.move_selection(1);
+        assert_eq!(palette.selected_item(), Some(2));
+        palette.move_selection(1);
+        assert_eq!(palette.selected_item(), Some(1));
+        palette.insert("x");
+        assert_eq!(palette.selected_item(), None);
+        palette.backspace();
+        palette.backspace();
+        palette.backspace();
+        assert_eq!(palette.query(), "");
+        assert_eq!(palette.matches().len(), 4);
+        palette.move_selection(2);
+        palette.extend(items(&["dock"]));
+        assert_eq!(palette.matches().len(), 5);
+        assert_eq!(palette.selected_item(), Some(2));
+    }
+}
@@ -1,33 +1,440 @@
+use std::path::{Path, PathBuf};
+
 use sdl2::{
     keyboard::Keycode,
-    pixels::{self, Color},
+    pixels::Color,
     rect::Rect,
-    render::Canvas,
-    surface::SurfaceRef,
+    render::{Canvas, Texture},
     video::Window,
     Sdl,
 };
 
-use crate::{atlas::Atlas, editor::Dimensions, text_buffer::Buffer};
+use crate::{
+    atlas::{Atlas, Style},
+    config::Colour,
+    cursors::{Cursor, Cursors},
+    editor::Dimensions,
+    file_tree::FileTree,
+    history::{self, UndoTree},
+    layout::{self, Bounds, Layout, Side},
+    palette::Palette,
+    text_buffer::{Buffer, LineChange, Shift},
+    wrap,
+};
+
+const PALETTE_ROWS: usize = 10;
+const PALETTE_WIDTH: u32 = 640;
+const PALETTE_MARGIN: i32 = 24;
+// how far towards the text colour panels and selections are from the background
+const PANEL_SHADE: f32 = 0.12;
+const SELECTION_SHADE: f32 = 0.28;
+// the gap between split panes
+const DIVIDER_WIDTH: u32 = 2;
+// the file tree never takes more than half the window
+const SIDEBAR_WIDTH: u32 = 240;
+// rows of the undo tree shown around the current state
+const UNDO_TREE_ROWS: usize = 16;
+
+// a span of columns [start, end) on a buffer line drawn in the given style,
+// anything not covered by a run is drawn as Style::Regular
+#[derive(Debug, PartialEq, Clone)]
+pub struct StyledRun {
+    pub line: u32,
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
 
-#[derive(Debug, PartialEq, PartialOrd, Clone)]
-struct Position {
-    x: u32,
-    y: u32,
+// what a buffer's view looks like when it isn't the one on screen, so
+// switching back puts everything where it was
+#[derive(Clone)]
+pub struct View {
+    cursors: Cursors,
+    top_line: u32,
+}
+
+impl View {
+    pub fn with_cursor(line: u32, column: u32) -> View {
+        View {
+            cursors: Cursors::new(Cursor::new((line, column))),
+            top_line: 0,
+        }
+    }
 }
 
-struct Cursor {
-    cursor_pos: Position,
-    old_cursor_pos: Position,
-    line_num: u32,
+// one part of the window showing a buffer, several can show the same one.
+// Positions are in buffer lines rather than rows on screen so cursors stay
+// put when the view scrolls. With wrapping on a line can take up several rows
+struct Pane {
+    // the path of the buffer it shows
+    buffer: PathBuf,
+    // where it sits in the text area below the tab bar
+    bounds: Bounds,
+    cursors: Cursors,
+    top_line: u32,
+    line_buf: Box<Vec<String>>,
+    styles: Vec<StyledRun>,
+    // highlighted ranges of (line, column), end exclusive
+    selections: Vec<((u32, u32), (u32, u32))>,
+    // a column selection as (anchor, head), its columns can go past the end
+    // of shorter lines. There is a cursor on each line it covers
+    block: Option<((u32, u32), (u32, u32))>,
+    // by line from top_line, all the rows of a wrapped line redraw together
+    dirty_lines: Vec<bool>,
+    full_redraw: bool,
+    wrap: bool,
+    // the column each row of the lines in line_buf starts at, worked out
+    // when drawing. Without wrapping every line is a single row
+    rows: Vec<Vec<usize>>,
+    // the primary cursor moved while wrapping, the view scrolls to it once
+    // draw_text can lay out the lines in between
+    follow_cursor: bool,
 }
 
 pub struct Screen {
-    cursor: Cursor,
+    panes: Vec<Pane>,
+    // the pane keyboard input goes to, the only one drawing its cursors
+    focused: usize,
+    layout: Layout,
+    cursor_visible: bool,
     canvas: Canvas<Window>,
     window_size: Dimensions,
-    top_line: u32,
-    line_buf: Box<Vec<String>>,
+    // where each tab was drawn last frame, for clicks
+    tab_spans: Vec<(i32, i32)>,
+    // the file tree down the left below the tab bar, when it's open. The
+    // first row shown scrolls to keep the selected one in view
+    sidebar: bool,
+    sidebar_first: usize,
+    // physical pixels per logical pixel, everything is laid out in logical
+    // pixels and only scaled up when it reaches the canvas
+    scale: f32,
+    // the atlas surface uploaded to the GPU, along with the id of the atlas it
+    // came from so it only gets re-uploaded when the atlas is replaced
+    glyph_texture: Option<(u32, Texture)>,
+    // holds the last frame so only rows that changed need drawing again
+    target: Option<Texture>,
+    line_height: u32,
+    // long lines carry on onto the rows below in every pane
+    wrap: bool,
+    // every pane and the dividers between them need drawing again
+    full_redraw: bool,
+    background: Color,
+    foreground: Color,
+    cursor_colour: Color,
+}
+
+impl Pane {
+    fn new(text_buffer: &Buffer, view: View, wrap: bool) -> Pane {
+        Pane {
+            buffer: text_buffer.path().to_path_buf(),
+            bounds: Bounds {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            },
+            cursors: view.cursors,
+            top_line: view.top_line,
+            line_buf: Box::new(text_buffer.get_lines(view.top_line, view.top_line + 1)),
+            styles: Vec::new(),
+            selections: Vec::new(),
+            block: None,
+            dirty_lines: Vec::new(),
+            full_redraw: true,
+            wrap,
+            rows: Vec::new(),
+            follow_cursor: wrap,
+        }
+    }
+
+    fn view(&self) -> View {
+        View {
+            cursors: self.cursors.clone(),
+            top_line: self.top_line,
+        }
+    }
+
+    // keeps the primary cursor in view and redraws the rows cursors are on.
+    // How many rows wrapped lines take needs the atlas, so then the
+    // scrolling waits for draw_text
+    fn cursor_moved(&mut self, line_height: u32, text_buffer: &Buffer) {
+        if self.wrap {
+            self.follow_cursor = true;
+        } else {
+            let line = self.cursors.primary().position.0;
+            let top_line =
+                layout::scroll_into_view(self.top_line, line, self.bounds.height, line_height);
+            self.set_top_line(top_line, text_buffer);
+        }
+        self.mark_cursors_dirty();
+    }
+
+    // scrolls as little as it can to show the primary cursor's row
+    fn scroll_to_cursor(&mut self, atlas: &Atlas, text_buffer: &Buffer) {
+        self.follow_cursor = false;
+        let (line, column) = self.cursors.primary().position;
+        if line <= self.top_line {
+            self.set_top_line(line, text_buffer);
+            return;
+        }
+        let visible_rows = (self.bounds.height / atlas.get_line_height().max(1)).max(1) as usize;
+        let rows_of = |line_num: u32| {
+            let line = text_buffer.get_lines(line_num, line_num + 1).pop();
+            self.wrap_line(atlas, &line.unwrap_or_default(), line_num)
+        };
+        // rows from the top of the cursor's line down to the cursor, then
+        // whole lines above it for as long as they fit
+        let mut rows = wrap::row_of(&rows_of(line), column as usize) + 1;
+        let mut top_line = line;
+        while top_line > self.top_line {
+            let above = rows_of(top_line - 1).len();
+            if rows + above > visible_rows {
+                break;
+            }
+            rows += above;
+            top_line -= 1;
+        }
+        self.set_top_line(top_line, text_buffer);
+    }
+
+    fn mark_cursors_dirty(&mut self) {
+        let lines: Vec<(u32, u32)> = self
+            .cursors
+            .all()
+            .iter()
+            .map(|cursor| match cursor.selection() {
+                Some((start, end)) => (start.0, end.0),
+                None => (cursor.position.0, cursor.position.0),
+            })
+            .collect();
+        for (first, last) in lines {
+            for line in first..=last {
+                self.mark_line_dirty(line);
+            }
+        }
+    }
+
+    fn set_top_line(&mut self, top_line: u32, text_buffer: &Buffer) {
+        if top_line == self.top_line {
+            return;
+        }
+        self.top_line = top_line;
+        self.line_buf = Box::new(text_buffer.get_lines(
+            self.top_line,
+            self.top_line + self.dirty_lines.len().max(1) as u32,
+        ));
+        self.rows.clear();
+        self.full_redraw = true;
+    }
+
+    fn mark_line_dirty(&mut self, line: u32) {
+        if line < self.top_line {
+            return;
+        }
+        if let Some(dirty) = self.dirty_lines.get_mut((line - self.top_line) as usize) {
+            *dirty = true;
+        }
+    }
+
+    fn lines_changed(&mut self, change: &LineChange) {
+        let last_line = change
+            .end
+            .unwrap_or(self.top_line + self.dirty_lines.len() as u32);
+        for line in change.start..last_line {
+            self.mark_line_dirty(line);
+        }
+    }
+
+    // moves the cursors and view of a pane the edit wasn't made in
+    fn shift(&mut self, shift: &Shift) {
+        let cursors = self
+            .cursors
+            .all()
+            .iter()
+            .map(|cursor| Cursor {
+                position: shift.apply(cursor.position),
+                anchor: cursor.anchor.map(|anchor| shift.apply(anchor)),
+                goal_column: cursor.goal_column,
+            })
+            .collect();
+        self.cursors.replace(cursors);
+        for (start, end) in self.selections.iter_mut() {
+            (*start, *end) = (shift.apply(*start), shift.apply(*end));
+        }
+        self.block = self
+            .block
+            .map(|(anchor, head)| (shift.apply(anchor), shift.apply(head)));
+        let top_line = shift.apply((self.top_line, 0)).0;
+        if top_line != self.top_line {
+            self.top_line = top_line;
+            self.rows.clear();
+            self.full_redraw = true;
+        }
+    }
+
+    fn layout_line(&self, atlas: &Atlas, line: &str, line_num: u32) -> Vec<i32> {
+        atlas.layout_line(line, |column| {
+            Screen::style_at(&self.styles, line_num, column)
+        })
+    }
+
+    // where the rows of a line start, a single row when not wrapping
+    fn wrap_line(&self, atlas: &Atlas, line: &str, line_num: u32) -> Vec<usize> {
+        match self.wrap {
+            true => {
+                let offsets = self.layout_line(atlas, line, line_num);
+                wrap::row_starts(line, &offsets, self.bounds.width as i32)
+            }
+            false => vec![0],
+        }
+    }
+
+    // works out the rows of every line shown, when a line now takes a
+    // different number of rows everything under it moves
+    fn layout_rows(&mut self, atlas: &Atlas) {
+        let rows: Vec<Vec<usize>> = (self.top_line..)
+            .zip(self.line_buf.iter())
+            .map(|(line_num, line)| self.wrap_line(atlas, line, line_num))
+            .collect();
+        let row_counts = |rows: &[Vec<usize>]| rows.iter().map(Vec::len).collect::<Vec<usize>>();
+        if row_counts(&rows) != row_counts(&self.rows) {
+            self.full_redraw = true;
+        }
+        self.rows = rows;
+    }
+
+    // the rows of the line at an index into line_buf
+    fn line_rows(&self, index: usize) -> &[usize] {
+        self.rows.get(index).map_or(&[0], Vec::as_slice)
+    }
+
+    // the x each row of a line is drawn from, the rows after the first move
+    // back to the left edge
+    fn row_offsets(&self, atlas: &Atlas, index: usize) -> Vec<i32> {
+        let starts = self.line_rows(index);
+        match (starts.len(), self.line_buf.get(index)) {
+            (2.., Some(line)) => {
+                let offsets = self.layout_line(atlas, line, self.top_line + index as u32);
+                starts
+                    .iter()
+                    .map(|start| offsets[(*start).min(offsets.len() - 1)])
+                    .collect()
+            }
+            _ => vec![0; starts.len()],
+        }
+    }
+
+    // where a position is drawn, relative to the pane
+    fn point_at(&self, atlas: &Atlas, (line_num, column): (u32, u32)) -> (i32, i32) {
+        let line_height = atlas.get_line_height() as i32;
+        let index = line_num.saturating_sub(self.top_line) as usize;
+        let rows_above: usize = (0..index).map(|index| self.line_rows(index).len()).sum();
+        let Some(line) = self.line_buf.get(index) else {
+            return (0, rows_above as i32 * line_height);
+        };
+        let offsets = self.layout_line(atlas, line, line_num);
+        let column = (column as usize).min(offsets.len() - 1);
+        let row = wrap::row_of(self.line_rows(index), column);
+        let x = offsets[column] - self.row_offsets(atlas, index)[row];
+        (x, (rows_above + row) as i32 * line_height)
+    }
+
+    // the x ranges of a line that are selected, a selection carrying on past
+    // the end of the line covers a space's width for the newline
+    fn selection_spans(&self, atlas: &Atlas, line: &str, line_num: u32) -> Vec<(i32, i32)> {
+        let mut spans = Vec::new();
+        let mut offsets = None;
+        if let Some((anchor, head)) = self.block {
+            if anchor.0.min(head.0) <= line_num && line_num <= anchor.0.max(head.0) {
+                let offsets = self.layout_line(atlas, line, line_num);
+                // past the end of the line columns are a space wide
+                let space = atlas.get_glyph(&' ', Style::Regular).advance;
+                let x_at = |column: u32| match offsets.get(column as usize) {
+                    Some(x) => *x,
+                    None => {
+                        offsets[offsets.len() - 1]
+                            + (column as i32 - offsets.len() as i32 + 1) * space
+                    }
+                };
+                let (from, to) = (x_at(anchor.1.min(head.1)), x_at(anchor.1.max(head.1)));
+                if to > from {
+                    spans.push((from, to));
+                }
+            }
+        }
+        let cursor_selections = self
+            .cursors
+            .all()
+            .iter()
+            .filter(|_| self.block.is_none())
+            .filter_map(Cursor::selection);
+        for (start, end) in self.selections.iter().copied().chain(cursor_selections) {
+            if line_num < start.0 || line_num > end.0 {
+                continue;
+            }
+            let offsets = offsets.get_or_insert_with(|| self.layout_line(atlas, line, line_num));
+            let x_at = |column: u32| offsets[(column as usize).min(offsets.len() - 1)];
+            let from = if line_num == start.0 {
+                x_at(start.1)
+            } else {
+                0
+            };
+            let to = if line_num == end.0 {
+                x_at(end.1)
+            } else {
+                x_at(u32::MAX) + atlas.get_glyph(&' ', Style::Regular).advance
+            };
+            if to > from {
+                spans.push((from, to));
+            }
+        }
+        spans
+    }
+
+    // like Screen::position_at but relative to the pane
+    fn virtual_position_at(&self, x: i32, y: i32, atlas: &Atlas) -> Option<(u32, u32)> {
+        if self.line_buf.is_empty() {
+            return None;
+        }
+        // the line whose rows cover y, the last line takes anything below
+        let mut row = (y.max(0) as u32 / atlas.get_line_height()) as usize;
+        let mut index = 0;
+        while index + 1 < self.line_buf.len() && row >= self.line_rows(index).len() {
+            row -= self.line_rows(index).len();
+            index += 1;
+        }
+        let starts = self.line_rows(index);
+        let row = row.min(starts.len() - 1);
+        let line_num = self.top_line + index as u32;
+        let offsets = self.layout_line(atlas, &self.line_buf[index], line_num);
+        let x = x + self.row_offsets(atlas, index)[row];
+        let start = starts[row].min(offsets.len() - 1);
+        let last = match starts.get(row + 1) {
+            Some(next) => (next - 1).min(offsets.len() - 1),
+            None => {
+                let line_end = offsets[offsets.len() - 1];
+                if x > line_end {
+                    let space = atlas.get_glyph(&' ', Style::Regular).advance.max(1);
+                    let past_end = (x - line_end + space / 2) / space;
+                    return Some((line_num, (offsets.len() - 1) as u32 + past_end as u32));
+                }
+                offsets.len() - 1
+            }
+        };
+        let column = (start..=last)
+            .min_by_key(|column| (offsets[*column] - x).abs())
+            .unwrap_or(start);
+        Some((line_num, column as u32))
+    }
+}
+
+// everything one pane needs drawn this frame, in physical pixels
+struct PaneDrawing {
+    clip: Rect,
+    clears: Vec<Rect>,
+    highlights: Vec<Rect>,
+    glyphs: Vec<(Rect, Rect)>,
+    cursor_rects: Vec<Rect>,
 }
 
 impl Screen {
@@ -41,153 +448,1098 @@ impl Screen {
         let window = video_subsystem
             .window("Text Editor", dimensions.width, dimensions.height)
             .position_centered()
+            .resizable()
+            .allow_highdpi()
             .build()
             .map_err(|e| e.to_string())?;
 
-        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        let mut canvas = window
+            .into_canvas()
+            .present_vsync()
+            .target_texture()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let (drawable_width, _) = canvas.output_size()?;
+        let scale = drawable_width as f32 / dimensions.width as f32;
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        Ok(Screen {
-            cursor: Cursor {
-                cursor_pos: Position { x: 0, y: 0 },
-                old_cursor_pos: Position { x: 0, y: 0 },
-                line_num: 0,
-            },
+        canvas.set_draw_color(Color::BLACK);
+        let mut screen = Screen {
+            panes: vec![Pane::new(text_buffer, View::with_cursor(0, 0), false)],
+            focused: 0,
+            layout: Layout::new(),
+            cursor_visible: true,
             canvas,
-            top_line: 0,
             window_size: Dimensions {
                 height: dimensions.height,
                 width: dimensions.width,
             },
-            line_buf: Box::new(text_buffer.get_lines(0, 1)),
-        })
+            tab_spans: Vec::new(),
+            sidebar: false,
+            sidebar_first: 0,
+            scale,
+            glyph_texture: None,
+            target: None,
+            line_height: 1,
+            wrap: false,
+            full_redraw: true,
+            background: Color::BLACK,
+            foreground: Color::WHITE,
+            cursor_colour: Color::WHITE,
+        };
+        screen.relayout();
+        Ok(screen)
+    }
+
+    pub fn get_scale(&self) -> f32 {
+        self.scale
+    }
+
+    fn pane(&self) -> &Pane {
+        &self.panes[self.focused]
     }
 
-    pub fn draw_text<S>(&mut self, text_buffer: &mut Buffer, surface: S, atlas: &Atlas)
+    fn pane_mut(&mut self) -> &mut Pane {
+        &mut self.panes[self.focused]
+    }
+
+    // redraws the rows that changed since the last frame into the render
+    // target and copies the whole target onto the window. buffer_for finds
+    // the buffer a pane shows from its path, styles_for gives the styled runs
+    // of one of its lines. Styles only depend on the line so a row is
+    // restyled whenever it's redrawn
+    pub fn draw_text<'a, F, S>(&mut self, buffer_for: F, styles_for: S, atlas: &Atlas)
     where
-        S: AsRef<SurfaceRef>,
+        F: Fn(&Path) -> Option<&'a Buffer>,
+        S: Fn(&Path, u32, &str) -> Vec<StyledRun>,
     {
-        let texture_creator = self.canvas.texture_creator();
-        let mut texture = texture_creator
-            .create_texture_from_surface(surface)
-            .unwrap();
+        let line_height_changed = self.line_height != atlas.get_line_height();
+        if self.upload_atlas(atlas) || line_height_changed {
+            self.line_height = atlas.get_line_height();
+            self.relayout();
+        }
+        // cursors placed before the font was loaded, or before it changed
+        // size, were scrolled to for rows of the old height
+        if line_height_changed {
+            let line_height = self.line_height;
+            for pane in self.panes.iter_mut() {
+                if let Some(text_buffer) = buffer_for(&pane.buffer) {
+                    pane.cursor_moved(line_height, text_buffer);
+                }
+            }
+        }
+        for pane in self.panes.iter_mut() {
+            // every line is at least a row, so there can't be more shown
+            let num_lines = pane.bounds.height.div_ceil(self.line_height);
+            pane.dirty_lines.resize(num_lines as usize, true);
+            if let Some(text_buffer) = buffer_for(&pane.buffer) {
+                if pane.follow_cursor {
+                    pane.scroll_to_cursor(atlas, text_buffer);
+                }
+                let line_buf = text_buffer.get_lines(pane.top_line, pane.top_line + num_lines);
+                pane.line_buf = Box::new(line_buf);
+            }
+            pane.styles = (pane.top_line..)
+                .zip(pane.line_buf.iter())
+                .flat_map(|(line_num, line)| styles_for(&pane.buffer, line_num, line))
+                .collect();
+            pane.layout_rows(atlas);
+        }
 
-        texture.set_color_mod(255, 255, 255);
+        if self.target.is_none() {
+            let (width, height) = self.canvas.output_size().unwrap();
+            let target = self
+                .canvas
+                .texture_creator()
+                .create_texture_target(None, width, height)
+                .unwrap();
+            self.target = Some(target);
+            self.full_redraw = true;
+        }
 
-        let mut dst = Rect::new(0, self.top_line as i32, 0, 0);
+        // work out everything that needs drawing first so the target canvas
+        // only has to borrow the textures
+        let drawings: Vec<PaneDrawing> = (0..self.panes.len())
+            .map(|index| self.pane_drawing(index, atlas))
+            .collect();
+        let dividers: Vec<Rect> = match self.full_redraw {
+            true => self
+                .layout
+                .dividers(self.text_area(), DIVIDER_WIDTH)
+                .iter()
+                .map(|bounds| self.physical_rect(bounds))
+                .collect(),
+            false => Vec::new(),
+        };
+        self.full_redraw = false;
+        for pane in self.panes.iter_mut() {
+            pane.full_redraw = false;
+            pane.dirty_lines.fill(false);
+        }
 
-        let char_size = atlas.get_font_size();
-        let chars_wide = self.window_size.width.div_ceil(char_size.width);
-        let num_lines = self.window_size.height.div_ceil(char_size.height);
+        let (selection_colour, divider_colour) =
+            (self.shade(SELECTION_SHADE), self.shade(PANEL_SHADE));
+        let glyph_texture = &self.glyph_texture.as_ref().unwrap().1;
+        let target = self.target.as_mut().unwrap();
+        let (background, cursor_colour) = (self.background, self.cursor_colour);
+        self.canvas
+            .with_texture_canvas(target, |canvas| {
+                canvas.set_draw_color(divider_colour);
+                for rect in &dividers {
+                    canvas.fill_rect(*rect).unwrap();
+                }
+                for drawing in &drawings {
+                    canvas.set_clip_rect(drawing.clip);
+                    canvas.set_draw_color(background);
+                    for rect in &drawing.clears {
+                        canvas.fill_rect(*rect).unwrap();
+                    }
+                    canvas.set_draw_color(selection_colour);
+                    for rect in &drawing.highlights {
+                        canvas.fill_rect(*rect).unwrap();
+                    }
+                    for (src, dst) in &drawing.glyphs {
+                        canvas.copy(glyph_texture, *src, *dst).unwrap();
+                    }
+                    canvas.set_draw_color(cursor_colour);
+                    for rect in &drawing.cursor_rects {
+                        canvas.fill_rect(*rect).unwrap();
+                    }
+                }
+                canvas.set_clip_rect(None);
+            })
+            .map_err(|e| e.to_string())
+            .unwrap();
+        // the text goes below the tab bar
+        let (width, height) = self.canvas.output_size().unwrap();
+        let top = self.to_physical(self.line_height as i32).min(height as i32);
+        let text_height = height - top as u32;
+        self.canvas
+            .copy(
+                self.target.as_ref().unwrap(),
+                Rect::new(0, 0, width, text_height),
+                Rect::new(0, top, width, text_height),
+            )
+            .unwrap();
+    }
 
-        let line_buf = text_buffer.get_lines(self.top_line, self.top_line + num_lines);
-        self.line_buf = Box::new(line_buf);
-        for line in &*self.line_buf {
-            dst.set_x(0);
-            for (index, character) in line.as_bytes().iter().enumerate() {
-                if index > chars_wide.try_into().unwrap() {
-                    break;
+    fn pane_drawing(&self, index: usize, atlas: &Atlas) -> PaneDrawing {
+        let pane = &self.panes[index];
+        let bounds = pane.bounds;
+        let mut drawing = PaneDrawing {
+            clip: self.physical_rect(&bounds),
+            clears: Vec::new(),
+            highlights: Vec::new(),
+            glyphs: Vec::new(),
+            cursor_rects: Vec::new(),
+        };
+        let full_redraw = self.full_redraw || pane.full_redraw;
+        let line_height = self.line_height as i32;
+        let bottom = bounds.y + bounds.height as i32;
+        let mut y = bounds.y;
+        for index in 0..pane.dirty_lines.len() {
+            if y >= bottom {
+                break;
+            }
+            let starts = pane.line_rows(index);
+            let height = starts.len() as i32 * line_height;
+            let row_y = y;
+            y += height;
+            if !full_redraw && !pane.dirty_lines[index] {
+                continue;
+            }
+            drawing.clears.push(Rect::new(
+                self.to_physical(bounds.x),
+                self.to_physical(row_y),
+                self.to_physical(bounds.width as i32) as u32,
+                self.to_physical(height) as u32,
+            ));
+            let line_num = pane.top_line + index as u32;
+            if let Some(line) = pane.line_buf.get(index) {
+                // everything is laid out along the whole line, then each
+                // row is moved back to the left edge
+                let row_x = pane.row_offsets(atlas, index);
+                for (start, end) in pane.selection_spans(atlas, line, line_num) {
+                    for (row, x) in row_x.iter().enumerate() {
+                        let row_end = row_x.get(row + 1).copied().unwrap_or(i32::MAX);
+                        let (from, to) = (start.max(*x), end.min(row_end));
+                        if to > from {
+                            drawing.highlights.push(Rect::new(
+                                self.to_physical(bounds.x + from - x),
+                                self.to_physical(row_y + row as i32 * line_height),
+                                self.to_physical(to - from) as u32,
+                                self.to_physical(line_height) as u32,
+                            ));
+                        }
+                    }
+                }
+                let style_at = |column| Self::style_at(&pane.styles, line_num, column);
+                for piece in atlas.shape_line(line, style_at) {
+                    let row = wrap::row_of(starts, piece.column);
+                    let x = piece.x - row_x[row];
+                    if x > bounds.width as i32 {
+                        match row + 1 == starts.len() {
+                            true => break,
+                            false => continue,
+                        }
+                    }
+                    let Some(glyph) = piece.glyph else {
+                        continue;
+                    };
+                    // glyphs were rasterized at the physical size so only their
+                    // position needs scaling
+                    let dst = Rect::new(
+                        self.to_physical(bounds.x + x + glyph.bearing_x.min(0)),
+                        self.to_physical(row_y + row as i32 * line_height),
+                        glyph.src.width(),
+                        glyph.src.height(),
+                    );
+                    drawing.glyphs.push((glyph.src, dst));
+                }
+            }
+            if self.cursor_visible && index == self.focused {
+                for cursor in pane.cursors.all() {
+                    if cursor.position.0 == line_num {
+                        drawing
+                            .cursor_rects
+                            .push(self.cursor_rect(pane, atlas, cursor.position));
+                    }
                 }
-                let src_rect = atlas.get_char(&(*character as char));
-                dst.set_width(src_rect.width());
-                dst.set_height(src_rect.height());
-                self.canvas.copy(&texture, src_rect, dst).unwrap();
-                dst.set_x(dst.x() + i32::try_from(src_rect.width()).unwrap());
             }
-            dst.set_y(dst.y() + i32::try_from(char_size.height).unwrap());
         }
+        drawing
     }
 
-    pub fn draw_cursor(&mut self, atlas: &Atlas) {
-        let font_size = atlas.get_font_size();
-        let coordinate_cursor_x = self.cursor.cursor_pos.x * font_size.width;
-        let coordinate_cursor_y = self.cursor.cursor_pos.y * font_size.height;
-        let mut width = 2;
-        if font_size.width >= 10 {
-            width = (font_size.width as f64 * 0.2).floor() as i32;
-            if width % 2 != 0 {
-                width -= 1;
+    // a row along the top with a tab for each open buffer, modified ones
+    // marked with a dot
+    pub fn draw_tabs(&mut self, tabs: &[(String, bool)], active: usize, atlas: &Atlas) {
+        let line_height = atlas.get_line_height() as i32;
+        let width = self.window_size.width as i32;
+        let mut spans = Vec::with_capacity(tabs.len());
+        let mut glyphs = Vec::new();
+        let mut x = 0;
+        for (name, modified) in tabs {
+            let label = match modified {
+                true => format!("{name} \u{2022}"),
+                false => name.clone(),
+            };
+            let label_width = *atlas
+                .layout_line(&label, |_| Style::Regular)
+                .last()
+                .unwrap();
+            let tab_width = label_width + line_height;
+            glyphs.extend(
+                self.text_rects(atlas, &label, x + line_height / 2, width, 0, |_| {
+                    Style::Regular
+                }),
+            );
+            spans.push((x, x + tab_width));
+            x += tab_width;
+        }
+
+        self.canvas.set_draw_color(self.shade(PANEL_SHADE));
+        self.canvas
+            .fill_rect(Rect::new(
+                0,
+                0,
+                self.to_physical(width) as u32,
+                self.to_physical(line_height) as u32,
+            ))
+            .unwrap();
+        if let Some((start, end)) = spans.get(active) {
+            self.canvas.set_draw_color(self.background);
+            self.canvas
+                .fill_rect(Rect::new(
+                    self.to_physical(*start),
+                    0,
+                    self.to_physical(end - start) as u32,
+                    self.to_physical(line_height) as u32,
+                ))
+                .unwrap();
+        }
+        let glyph_texture = &self.glyph_texture.as_ref().unwrap().1;
+        for (src, dst) in &glyphs {
+            self.canvas.copy(glyph_texture, *src, *dst).unwrap();
+        }
+        self.tab_spans = spans;
+    }
+
+    // the tab under a point in the window, if it's in the tab bar
+    pub fn tab_at(&self, x: i32, y: i32) -> Option<usize> {
+        if y < 0 || y >= self.line_height as i32 {
+            return None;
+        }
+        self.tab_spans
+            .iter()
+            .position(|(start, end)| *start <= x && x < *end)
+    }
+
+    // lists the file tree's rows with directories marked + when collapsed
+    // and - when expanded, the selected row is highlighted
+    pub fn draw_sidebar(&mut self, tree: &FileTree, focused: bool, atlas: &Atlas) {
+        let line_height = atlas.get_line_height() as i32;
+        let width = self.sidebar_width() as i32;
+        let top = line_height;
+        let visible_rows = ((self.window_size.height as i32 - top) / line_height).max(1) as usize;
+        if tree.selected() < self.sidebar_first {
+            self.sidebar_first = tree.selected();
+        } else if tree.selected() >= self.sidebar_first + visible_rows {
+            self.sidebar_first = tree.selected() + 1 - visible_rows;
+        }
+        self.sidebar_first = self
+            .sidebar_first
+            .min(tree.rows().len().saturating_sub(visible_rows));
+
+        let space = atlas.get_glyph(&' ', Style::Regular).advance;
+        let mut glyphs = Vec::new();
+        let mut selection = None;
+        let shown = tree.rows().iter().enumerate().skip(self.sidebar_first);
+        for (row_num, row) in shown.take(visible_rows) {
+            let y = top + (row_num - self.sidebar_first) as i32 * line_height;
+            if row_num == tree.selected() {
+                selection = Some(Rect::new(
+                    0,
+                    self.to_physical(y),
+                    self.to_physical(width) as u32,
+                    self.to_physical(line_height) as u32,
+                ));
             }
+            let name = row.path.file_name().unwrap_or_default().to_string_lossy();
+            let label = match (row.is_dir, row.expanded) {
+                (true, true) => format!("- {name}/"),
+                (true, false) => format!("+ {name}/"),
+                (false, _) => format!("  {name}"),
+            };
+            let x = space + row.depth as i32 * space * 2;
+            glyphs.extend(self.text_rects(atlas, &label, x, width, y, |_| Style::Regular));
         }
-        self.canvas.set_draw_color(pixels::Color {
-            r: 255,
-            g: 255,
-            b: 255,
-            a: 0,
-        });
+
+        let (panel_colour, selection_colour) =
+            (self.shade(PANEL_SHADE), self.shade(SELECTION_SHADE));
+        self.canvas.set_draw_color(panel_colour);
         self.canvas
             .fill_rect(Rect::new(
-                coordinate_cursor_x as i32 - (width / 2),
-                coordinate_cursor_y as i32,
-                width as u32,
-                font_size.height,
+                0,
+                self.to_physical(top),
+                self.to_physical(width) as u32,
+                self.to_physical(self.window_size.height as i32 - top)
+                    .max(0) as u32,
             ))
             .unwrap();
+        // a faint highlight shows where the selection is while typing goes
+        // to the text
+        if let Some(rect) = selection {
+            self.canvas.set_draw_color(match focused {
+                true => selection_colour,
+                false => self.shade((PANEL_SHADE + SELECTION_SHADE) / 2.0),
+            });
+            self.canvas.fill_rect(rect).unwrap();
+        }
+        let glyph_texture = &self.glyph_texture.as_ref().unwrap().1;
+        for (src, dst) in &glyphs {
+            self.canvas.copy(glyph_texture, *src, *dst).unwrap();
+        }
     }
 
-    pub fn render(&mut self) {
-        self.canvas.present();
+    // the file tree row under a point in the window, Some(None) when the
+    // point is in the sidebar but below its last row
+    pub fn sidebar_row_at(&self, x: i32, y: i32, row_count: usize) -> Option<Option<usize>> {
+        let y = y - self.line_height as i32;
+        if x < 0 || x >= self.sidebar_width() as i32 || y < 0 {
+            return None;
+        }
+        let row = self.sidebar_first + (y / self.line_height as i32) as usize;
+        Some((row < row_count).then_some(row))
     }
 
-    pub fn clear_screen(&mut self) {
-        self.canvas.clear();
+    pub fn set_sidebar_visible(&mut self, visible: bool) {
+        if self.sidebar != visible {
+            self.sidebar = visible;
+            self.relayout();
+        }
     }
 
-    pub fn colour(&mut self, colour: Color) {
-        self.canvas.set_draw_color(colour);
+    fn sidebar_width(&self) -> u32 {
+        match self.sidebar {
+            true => SIDEBAR_WIDTH.min(self.window_size.width / 2),
+            false => 0,
+        }
     }
 
-    pub fn cursor_move(&mut self, direction: Keycode) {
-        match direction {
-            Keycode::LEFT => {
-                if self.cursor.cursor_pos.x != 0 {
-                    self.cursor.cursor_pos.x -= 1;
-                } else if self.cursor.cursor_pos.y > 0 {
-                    self.cursor.cursor_pos.y -= 1;
-                    self.cursor.cursor_pos.x =
-                        self.line_buf[self.cursor.cursor_pos.y as usize].len() as u32;
-                    self.cursor.line_num -= 1;
-                }
-                self.cursor.old_cursor_pos = self.cursor.cursor_pos.clone();
-            }
-            Keycode::RIGHT => {
-                if self.cursor.cursor_pos.x
-                    < self.line_buf[self.cursor.cursor_pos.y as usize].len() as u32
-                {
-                    self.cursor.cursor_pos.x += 1;
-                } else if self.cursor.cursor_pos.y < (self.line_buf.len() - 1) as u32 {
-                    self.cursor.cursor_pos.y += 1;
-                    self.cursor.cursor_pos.x = 0;
-                    self.cursor.line_num += 1;
-                }
-                self.cursor.old_cursor_pos = self.cursor.cursor_pos.clone();
+    // the part of the window below the tab bar and right of the file tree
+    // that panes share, in the render target's coordinates
+    fn text_area(&self) -> Bounds {
+        let sidebar_width = self.sidebar_width();
+        Bounds {
+            x: sidebar_width as i32,
+            y: 0,
+            width: self.window_size.width - sidebar_width,
+            height: self.window_size.height.saturating_sub(self.line_height),
+        }
+    }
+
+    // works out where every pane goes after the window or the layout changed
+    fn relayout(&mut self) {
+        let bounds = self.layout.bounds(self.text_area(), DIVIDER_WIDTH);
+        for (pane, bounds) in self.panes.iter_mut().zip(bounds) {
+            pane.bounds = bounds;
+            let num_lines = bounds.height.div_ceil(self.line_height);
+            pane.dirty_lines.resize(num_lines as usize, true);
+        }
+        self.full_redraw = true;
+    }
+
+    fn physical_rect(&self, bounds: &Bounds) -> Rect {
+        Rect::new(
+            self.to_physical(bounds.x),
+            self.to_physical(bounds.y),
+            self.to_physical(bounds.width as i32) as u32,
+            self.to_physical(bounds.height as i32) as u32,
+        )
+    }
+
+    // halves the focused pane, the new half shows the same buffer from the
+    // same place and takes the focus
+    pub fn split(&mut self, side: Side, text_buffer: &Buffer) {
+        let new_pane = self.panes.len();
+        let view = self.pane().view();
+        self.pane_mut().mark_cursors_dirty();
+        self.panes.push(Pane::new(text_buffer, view, self.wrap));
+        self.layout.split(self.focused, side, new_pane);
+        self.focused = new_pane;
+        self.relayout();
+    }
+
+    // the pane next to it takes over its space and the focus, returns false
+    // when it's the only pane
+    pub fn close_pane(&mut self) -> bool {
+        let closed = self.pane().bounds;
+        if !self.layout.remove(self.focused) {
+            return false;
+        }
+        self.panes.remove(self.focused);
+        self.focused = self.focused.min(self.panes.len() - 1);
+        self.relayout();
+        // whichever pane grew into the space now covers its middle
+        let middle = (
+            closed.x + closed.width as i32 / 2,
+            closed.y + closed.height as i32 / 2,
+        );
+        if let Some(pane) = self.pane_at(middle.0, middle.1) {
+            self.focused = pane;
+        }
+        true
+    }
+
+    // returns whether the focus moved
+    pub fn focus(&mut self, pane: usize) -> bool {
+        if pane == self.focused || pane >= self.panes.len() {
+            return false;
+        }
+        self.pane_mut().mark_cursors_dirty();
+        self.focused = pane;
+        self.pane_mut().mark_cursors_dirty();
+        true
+    }
+
+    pub fn focused_pane(&self) -> usize {
+        self.focused
+    }
+
+    pub fn pane_count(&self) -> usize {
+        self.panes.len()
+    }
+
+    // the pane under a point in the window
+    pub fn pane_at(&self, x: i32, y: i32) -> Option<usize> {
+        let y = y - self.line_height as i32;
+        self.panes
+            .iter()
+            .position(|pane| pane.bounds.contains(x, y))
+    }
+
+    // the nearest pane on one side of the focused one
+    pub fn neighbour(&self, side: Side) -> Option<usize> {
+        let bounds: Vec<Bounds> = self.panes.iter().map(|pane| pane.bounds).collect();
+        layout::neighbour(&bounds, self.focused, side)
+    }
+
+    // the path of the buffer the focused pane shows
+    pub fn buffer_path(&self) -> &Path {
+        &self.pane().buffer
+    }
+
+    // marks what an edit changed in every pane showing the buffer. The
+    // editor puts the focused pane's cursors where they go after an edit,
+    // the others are moved along with the text they were on
+    pub fn buffer_changed(&mut self, path: &Path, changes: &[LineChange]) {
+        for (index, pane) in self.panes.iter_mut().enumerate() {
+            if pane.buffer != path {
+                continue;
             }
-            Keycode::DOWN => {
-                if self.cursor.cursor_pos.y < (self.line_buf.len() - 1) as u32 {
-                    self.cursor.cursor_pos.y += 1;
-                    Self::set_cursor_x_on_vert_move(self);
-                    self.cursor.line_num += 1;
+            for change in changes {
+                pane.lines_changed(change);
+                if index != self.focused {
+                    pane.shift(&change.shift);
                 }
             }
-            Keycode::UP => {
-                if self.cursor.cursor_pos.y > 0 {
-                    self.cursor.cursor_pos.y -= 1;
-                    Self::set_cursor_x_on_vert_move(self);
-                    self.cursor.line_num -= 1;
-                }
+        }
+    }
+
+    // panes follow a buffer whose file moved
+    pub fn buffer_renamed(&mut self, from: &Path, to: &Path) {
+        for pane in self.panes.iter_mut().filter(|pane| pane.buffer == from) {
+            pane.buffer = to.to_path_buf();
+        }
+    }
+
+    // panes showing a buffer that was closed show another one instead
+    pub fn replace_buffer(&mut self, closed: &Path, text_buffer: &Buffer) {
+        for pane in self.panes.iter_mut() {
+            if pane.buffer == closed {
+                let bounds = pane.bounds;
+                *pane = Pane::new(text_buffer, View::with_cursor(0, 0), self.wrap);
+                pane.bounds = bounds;
             }
-            _ => unreachable!("method is only called for when keycode is a direction"),
         }
+        self.relayout();
     }
 
-    fn set_cursor_x_on_vert_move(&mut self) {
-        if self.line_buf[self.cursor.cursor_pos.y as usize].len()
-            >= self.cursor.old_cursor_pos.x as usize
-        {
-            self.cursor.cursor_pos.x = self.cursor.old_cursor_pos.x;
-        } else {
-            self.cursor.cursor_pos.x =
-                self.line_buf[self.cursor.cursor_pos.y as usize].len() as u32;
+    // draws the palette over the text, straight onto the window so the
+    // render target still holds the text underneath once it's closed
+    pub fn draw_palette(&mut self, palette: &Palette, atlas: &Atlas) {
+        let line_height = atlas.get_line_height() as i32;
+        let width = PALETTE_WIDTH.min(
+            self.window_size
+                .width
+                .saturating_sub(2 * PALETTE_MARGIN as u32),
+        );
+        let left = (self.window_size.width - width) as i32 / 2;
+        let text_left = left + line_height / 2;
+        let right = left + width as i32 - line_height / 2;
+        let first = palette.selected().saturating_sub(PALETTE_ROWS - 1);
+        let shown = &palette.matches()[first..palette.matches().len().min(first + PALETTE_ROWS)];
+
+        let panel = Rect::new(
+            self.to_physical(left),
+            self.to_physical(PALETTE_MARGIN),
+            self.to_physical(width as i32) as u32,
+            self.to_physical(line_height * (shown.len() as i32 + 1)) as u32,
+        );
+        let mut glyphs = self.text_rects(
+            atlas,
+            &format!("{} {}", palette.prompt(), palette.query()),
+            text_left,
+            right,
+            PALETTE_MARGIN,
+            |_| Style::Bold,
+        );
+        let mut selection = None;
+        for (row, found) in shown.iter().enumerate() {
+            let y = PALETTE_MARGIN + line_height * (row as i32 + 1);
+            if first + row == palette.selected() {
+                selection = Some(Rect::new(
+                    panel.x(),
+                    self.to_physical(y),
+                    panel.width(),
+                    self.to_physical(line_height) as u32,
+                ));
+            }
+            let item = &palette.items()[found.item];
+            // matched characters stand out in bold
+            let label =
+                self.text_rects(
+                    atlas,
+                    &item.label,
+                    text_left,
+                    right,
+                    y,
+                    |column| match found.positions.contains(&column) {
+                        true => Style::Bold,
+                        false => Style::Regular,
+                    },
+                );
+            let label_end = atlas.layout_line(&item.label, |_| Style::Regular);
+            let detail_left = text_left + label_end.last().unwrap() + line_height;
+            glyphs.extend(label);
+            glyphs.extend(
+                self.text_rects(atlas, &item.detail, detail_left, right, y, |_| {
+                    Style::Italic
+                }),
+            );
+        }
+
+        let (panel_colour, selection_colour) =
+            (self.shade(PANEL_SHADE), self.shade(SELECTION_SHADE));
+        self.canvas.set_draw_color(panel_colour);
+        self.canvas.fill_rect(panel).unwrap();
+        if let Some(rect) = selection {
+            self.canvas.set_draw_color(selection_colour);
+            self.canvas.fill_rect(rect).unwrap();
+        }
+        let glyph_texture = &self.glyph_texture.as_ref().unwrap().1;
+        for (src, dst) in &glyphs {
+            self.canvas.copy(glyph_texture, *src, *dst).unwrap();
+        }
+    }
+
+    // the undo history as a graph in the top right corner, newest at the top
+    // with the current state highlighted and each state's age beside it
+    pub fn draw_undo_tree(&mut self, tree: &UndoTree, atlas: &Atlas) {
+        let line_height = atlas.get_line_height() as i32;
+        let graph = tree.graph();
+        let current_row = graph
+            .iter()
+            .position(|(_, node)| *node == Some(tree.current()))
+            .unwrap_or(0);
+        let first = current_row
+            .saturating_sub(UNDO_TREE_ROWS / 2)
+            .min(graph.len().saturating_sub(UNDO_TREE_ROWS));
+        let shown = &graph[first..graph.len().min(first + UNDO_TREE_ROWS)];
+        // the numbers line up after the widest part of the graph
+        let graph_width = shown
+            .iter()
+            .map(|(lanes, _)| lanes.len())
+            .max()
+            .unwrap_or(0);
+        let now = history::now();
+        let labels: Vec<String> = shown
+            .iter()
+            .map(|(lanes, node)| match node {
+                Some(0) => format!("{lanes:graph_width$}  0 original"),
+                Some(node) => format!(
+                    "{lanes:graph_width$}  {node} {}",
+                    history::age(tree.nodes()[*node].time, now)
+                ),
+                None => lanes.clone(),
+            })
+            .collect();
+
+        let text_width = labels
+            .iter()
+            .map(|label| *atlas.layout_line(label, |_| Style::Regular).last().unwrap())
+            .max()
+            .unwrap_or(0);
+        let width = (text_width + line_height).min(self.window_size.width as i32 / 2);
+        let left = self.window_size.width as i32 - width - PALETTE_MARGIN;
+        let top = line_height + PALETTE_MARGIN;
+        let text_left = left + line_height / 2;
+        let right = left + width - line_height / 2;
+        let panel = Rect::new(
+            self.to_physical(left),
+            self.to_physical(top),
+            self.to_physical(width) as u32,
+            self.to_physical(line_height * (labels.len() as i32 + 1)) as u32,
+        );
+        let mut glyphs =
+            self.text_rects(atlas, "undo tree", text_left, right, top, |_| Style::Bold);
+        let mut selection = None;
+        for (row, label) in labels.iter().enumerate() {
+            let y = top + line_height * (row as i32 + 1);
+            if first + row == current_row {
+                selection = Some(Rect::new(
+                    panel.x(),
+                    self.to_physical(y),
+                    panel.width(),
+                    self.to_physical(line_height) as u32,
+                ));
+            }
+            glyphs.extend(self.text_rects(atlas, label, text_left, right, y, |_| Style::Regular));
+        }
+
+        let (panel_colour, selection_colour) =
+            (self.shade(PANEL_SHADE), self.shade(SELECTION_SHADE));
+        self.canvas.set_draw_color(panel_colour);
+        self.canvas.fill_rect(panel).unwrap();
+        if let Some(rect) = selection {
+            self.canvas.set_draw_color(selection_colour);
+            self.canvas.fill_rect(rect).unwrap();
+        }
+        let glyph_texture = &self.glyph_texture.as_ref().unwrap().1;
+        for (src, dst) in &glyphs {
+            self.canvas.copy(glyph_texture, *src, *dst).unwrap();
+        }
+    }
+
+    // a short line of text in the bottom right corner, like the editing mode
+    pub fn draw_status(&mut self, text: &str, atlas: &Atlas) {
+        let line_height = atlas.get_line_height() as i32;
+        let text_width = *atlas.layout_line(text, |_| Style::Regular).last().unwrap();
+        let width = text_width + line_height;
+        let left = self.window_size.width as i32 - width;
+        let top = self.window_size.height as i32 - line_height;
+        let panel = Rect::new(
+            self.to_physical(left),
+            self.to_physical(top),
+            self.to_physical(width) as u32,
+            self.to_physical(line_height) as u32,
+        );
+        let glyphs = self.text_rects(
+            atlas,
+            text,
+            left + line_height / 2,
+            self.window_size.width as i32,
+            top,
+            |_| Style::Regular,
+        );
+        self.canvas.set_draw_color(self.shade(PANEL_SHADE));
+        self.canvas.fill_rect(panel).unwrap();
+        let glyph_texture = &self.glyph_texture.as_ref().unwrap().1;
+        for (src, dst) in &glyphs {
+            self.canvas.copy(glyph_texture, *src, *dst).unwrap();
+        }
+    }
+
+    pub fn set_selections(&mut self, selections: Vec<((u32, u32), (u32, u32))>) {
+        let pane = self.pane_mut();
+        if selections == pane.selections {
+            return;
+        }
+        let changed: Vec<(u32, u32)> = pane
+            .selections
+            .iter()
+            .chain(&selections)
+            .map(|(start, end)| (start.0, end.0))
+            .collect();
+        for (first, last) in changed {
+            for line in first..=last {
+                pane.mark_line_dirty(line);
+            }
+        }
+        pane.selections = selections;
+    }
+
+    fn shade(&self, amount: f32) -> Color {
+        let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount) as u8;
+        Color::RGB(
+            channel(self.background.r, self.foreground.r),
+            channel(self.background.g, self.foreground.g),
+            channel(self.background.b, self.foreground.b),
+        )
+    }
+
+    // source and destination rects for a piece of text starting at x, cut off
+    // at max_x
+    fn text_rects<F>(
+        &self,
+        atlas: &Atlas,
+        text: &str,
+        x: i32,
+        max_x: i32,
+        y: i32,
+        style_at: F,
+    ) -> Vec<(Rect, Rect)>
+    where
+        F: Fn(usize) -> Style,
+    {
+        atlas
+            .shape_line(text, style_at)
+            .into_iter()
+            .take_while(|piece| x + piece.x + piece.advance <= max_x)
+            .filter_map(|piece| {
+                let glyph = piece.glyph?;
+                let dst = Rect::new(
+                    self.to_physical(x + piece.x + glyph.bearing_x.min(0)),
+                    self.to_physical(y),
+                    glyph.src.width(),
+                    glyph.src.height(),
+                );
+                Some((glyph.src, dst))
+            })
+            .collect()
+    }
+
+    fn cursor_rect(&self, pane: &Pane, atlas: &Atlas, (line, column): (u32, u32)) -> Rect {
+        let line_height = atlas.get_line_height();
+        let (x, y) = pane.point_at(atlas, (line, column));
+        let (coordinate_cursor_x, coordinate_cursor_y) = (pane.bounds.x + x, pane.bounds.y + y);
+        let char_width = atlas.get_glyph(&' ', Style::Regular).advance;
+        let mut width = 2;
+        if char_width >= 10 {
+            width = (char_width as f64 * 0.2).floor() as i32;
+            if width % 2 != 0 {
+                width -= 1;
+            }
+        }
+        Rect::new(
+            self.to_physical(coordinate_cursor_x - (width / 2)),
+            self.to_physical(coordinate_cursor_y),
+            self.to_physical(width) as u32,
+            self.to_physical(line_height as i32) as u32,
+        )
+    }
+
+    pub fn render(&mut self) {
+        self.canvas.present();
+    }
+
+    pub fn set_colours(&mut self, background: Colour, foreground: Colour, cursor: Colour) {
+        let to_color = |colour: Colour| Color::RGB(colour.r, colour.g, colour.b);
+        self.background = to_color(background);
+        self.foreground = to_color(foreground);
+        self.cursor_colour = to_color(cursor);
+        // the glyphs in the atlas are white so tinting them gives the text colour
+        if let Some((_, texture)) = self.glyph_texture.as_mut() {
+            texture.set_color_mod(self.foreground.r, self.foreground.g, self.foreground.b);
+        }
+        self.full_redraw = true;
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        if self.cursor_visible != visible {
+            self.cursor_visible = visible;
+            self.pane_mut().mark_cursors_dirty();
+        }
+    }
+
+    // where the primary cursor is
+    pub fn cursor_position(&self) -> (u32, u32) {
+        self.primary_cursor().position
+    }
+
+    pub fn primary_cursor(&self) -> &Cursor {
+        self.pane().cursors.primary()
+    }
+
+    pub fn cursors(&self) -> &[Cursor] {
+        self.pane().cursors.all()
+    }
+
+    // places a single cursor after an edit, clamped to the buffer
+    pub fn set_cursor(&mut self, line: u32, column: u32, text_buffer: &Buffer) {
+        let pane = self.pane_mut();
+        pane.mark_cursors_dirty();
+        pane.block = None;
+        let position = Self::clamp(text_buffer, (line, column));
+        pane.cursors.set(Cursor::new(position));
+        self.cursor_moved(text_buffer);
+    }
+
+    // swaps in one new cursor for each current one, in the same order
+    pub fn replace_cursors(&mut self, cursors: Vec<Cursor>, text_buffer: &Buffer) {
+        let pane = self.pane_mut();
+        pane.mark_cursors_dirty();
+        pane.block = None;
+        let cursors = cursors
+            .into_iter()
+            .map(|cursor| Cursor {
+                position: Self::clamp(text_buffer, cursor.position),
+                anchor: cursor.anchor.map(|anchor| Self::clamp(text_buffer, anchor)),
+                goal_column: cursor.goal_column,
+            })
+            .collect();
+        pane.cursors.replace(cursors);
+        self.cursor_moved(text_buffer);
+    }
+
+    // the new cursor becomes the primary one
+    pub fn add_cursor(&mut self, cursor: Cursor, text_buffer: &Buffer) {
+        let pane = self.pane_mut();
+        pane.mark_cursors_dirty();
+        pane.block = None;
+        pane.cursors.add(Cursor {
+            position: Self::clamp(text_buffer, cursor.position),
+            ..cursor
+        });
+        self.cursor_moved(text_buffer);
+    }
+
+    pub fn view(&self) -> View {
+        self.pane().view()
+    }
+
+    // shows another buffer in the focused pane, with its cursors and scroll
+    // where they were left
+    pub fn set_view(&mut self, view: View, text_buffer: &Buffer) {
+        let pane = self.pane_mut();
+        pane.buffer = text_buffer.path().to_path_buf();
+        pane.cursors = view.cursors;
+        pane.selections.clear();
+        pane.top_line = view.top_line.min(text_buffer.line_count() - 1);
+        pane.line_buf = Box::new(text_buffer.get_lines(
+            pane.top_line,
+            pane.top_line + pane.dirty_lines.len().max(1) as u32,
+        ));
+        pane.rows.clear();
+        pane.full_redraw = true;
+        let cursors = pane.cursors.all().to_vec();
+        self.replace_cursors(cursors, text_buffer);
+    }
+
+    pub fn block(&self) -> Option<((u32, u32), (u32, u32))> {
+        self.pane().block
+    }
+
+    // selects a rectangle with a cursor on each of its lines, the one on the
+    // head's line is primary
+    pub fn set_block(&mut self, anchor: (u32, u32), head: (u32, u32), text_buffer: &Buffer) {
+        let last_line = text_buffer.line_count() - 1;
+        let (anchor, head) = (
+            (anchor.0.min(last_line), anchor.1),
+            (head.0.min(last_line), head.1),
+        );
+        let lines = anchor.0.min(head.0)..=anchor.0.max(head.0);
+        let cursors = lines
+            .clone()
+            .map(|line| {
+                let len = text_buffer.line_len(line);
+                Cursor {
+                    goal_column: head.1,
+                    ..Cursor::with_anchor((line, head.1.min(len)), (line, anchor.1.min(len)))
+                }
+            })
+            .collect();
+        let pane = self.pane_mut();
+        pane.mark_cursors_dirty();
+        pane.cursors
+            .set_all(cursors, (head.0 - lines.start()) as usize);
+        pane.block = Some((anchor, head));
+        self.cursor_moved(text_buffer);
+    }
+
+    fn clamp(text_buffer: &Buffer, (line, column): (u32, u32)) -> (u32, u32) {
+        let line = line.min(text_buffer.line_count() - 1);
+        (line, column.min(text_buffer.line_len(line)))
+    }
+
+    // the window was resized, everything gets laid out again for the new size
+    // and the view scrolls if needed so the cursor is still visible
+    pub fn resize(&mut self, width: u32, height: u32, text_buffer: &Buffer) {
+        self.window_size = Dimensions { height, width };
+        if let Ok((drawable_width, _)) = self.canvas.output_size() {
+            self.scale = drawable_width as f32 / width.max(1) as f32;
+        }
+        if let Some(target) = self.target.take() {
+            // the canvas owning the texture is still alive since we own it
+            unsafe { target.destroy() };
+        }
+        self.relayout();
+        self.cursor_moved(text_buffer);
+    }
+
+    // moves the view by a number of lines without moving the cursor
+    pub fn scroll(&mut self, lines: i32, text_buffer: &Buffer) {
+        let max_top = text_buffer.line_count().saturating_sub(1) as i64;
+        let pane = self.pane_mut();
+        let top_line = (pane.top_line as i64 + lines as i64).clamp(0, max_top) as u32;
+        pane.follow_cursor = false;
+        pane.set_top_line(top_line, text_buffer);
+    }
+
+    // long lines carry on onto the rows below them, or run off the right
+    // of the pane
+    pub fn toggle_wrap(&mut self) {
+        self.wrap = !self.wrap;
+        for pane in self.panes.iter_mut() {
+            pane.wrap = self.wrap;
+            pane.rows.clear();
+            pane.full_redraw = true;
+            pane.follow_cursor = self.wrap;
         }
     }
+
+    // moves every cursor a character or line, dropping their selections
+    pub fn cursor_move(&mut self, direction: Keycode, text_buffer: &Buffer) {
+        let last_line = text_buffer.line_count() - 1;
+        let cursors = self
+            .cursors()
+            .iter()
+            .map(|cursor| {
+                let (line, column) = cursor.position;
+                match direction {
+                    Keycode::LEFT if column > 0 => Cursor::new((line, column - 1)),
+                    Keycode::LEFT if line > 0 => {
+                        Cursor::new((line - 1, text_buffer.line_len(line - 1)))
+                    }
+                    Keycode::RIGHT if column < text_buffer.line_len(line) => {
+                        Cursor::new((line, column + 1))
+                    }
+                    Keycode::RIGHT if line < last_line => Cursor::new((line + 1, 0)),
+                    Keycode::DOWN | Keycode::UP => {
+                        let line = match direction {
+                            Keycode::DOWN => (line + 1).min(last_line),
+                            _ => line.saturating_sub(1),
+                        };
+                        // keep heading for the column the cursor started from
+                        Cursor {
+                            position: (line, cursor.goal_column.min(text_buffer.line_len(line))),
+                            anchor: None,
+                            goal_column: cursor.goal_column,
+                        }
+                    }
+                    Keycode::LEFT | Keycode::RIGHT => Cursor::new(cursor.position),
+                    _ => unreachable!("method is only called for when keycode is a direction"),
+                }
+            })
+            .collect();
+        self.replace_cursors(cursors, text_buffer);
+    }
+
+    // the buffer position in the focused pane closest to a point in the window
+    pub fn position_at(&self, x: i32, y: i32, atlas: &Atlas) -> Option<(u32, u32)> {
+        let (line_num, column) = self.virtual_position_at(x, y, atlas)?;
+        let pane = self.pane();
+        let row = (line_num - pane.top_line) as usize;
+        Some((
+            line_num,
+            column.min(pane.line_buf[row].chars().count() as u32),
+        ))
+    }
+
+    // like position_at but past the end of a line it keeps counting columns
+    // a space wide, for column selections
+    pub fn virtual_position_at(&self, x: i32, y: i32, atlas: &Atlas) -> Option<(u32, u32)> {
+        let bounds = self.pane().bounds;
+        let y = y - self.line_height as i32 - bounds.y;
+        self.pane().virtual_position_at(x - bounds.x, y, atlas)
+    }
+
+    // moves the cursor to the character boundary closest to the clicked
+    // point, or adds another cursor there
+    pub fn cursor_click(&mut self, x: i32, y: i32, atlas: &Atlas, add: bool, text_buffer: &Buffer) {
+        let Some(position) = self.position_at(x, y, atlas) else {
+            return;
+        };
+        match add {
+            true => self.add_cursor(Cursor::new(position), text_buffer),
+            false => self.set_cursor(position.0, position.1, text_buffer),
+        }
+    }
+
+    fn cursor_moved(&mut self, text_buffer: &Buffer) {
+        let line_height = self.line_height;
+        self.pane_mut().cursor_moved(line_height, text_buffer);
+    }
+
+    // returns whether a new texture had to be uploaded
+    fn upload_atlas(&mut self, atlas: &Atlas) -> bool {
+        if let Some((id, _)) = self.glyph_texture {
+            if id == atlas.get_id() {
+                return false;
+            }
+        }
+        let mut texture = self
+            .canvas
+            .texture_creator()
+            .create_texture_from_surface(atlas.surface())
+            .unwrap();
+        texture.set_color_mod(self.foreground.r, self.foreground.g, self.foreground.b);
+        if let Some((_, old_texture)) = self.glyph_texture.replace((atlas.get_id(), texture)) {
+            // the canvas owning the texture is still alive since we own it
+            unsafe { old_texture.destroy() };
+        }
+        true
+    }
+
+    fn to_physical(&self, logical: i32) -> i32 {
+        (logical as f32 * self.scale).round() as i32
+    }
+
+    fn style_at(styles: &[StyledRun], line_num: u32, column: usize) -> Style {
+        styles
+            .iter()
+            .rev()
+            .find(|run| run.line == line_num && run.start <= column && column < run.end)
+            .map(|run| run.style)
+            .unwrap_or_default()
+    }
 }
@@ -0,0 +1,205 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8] = b"text_editor swap 1\n";
+
+// a change to the text as the journal stores it, offsets are into the
+// whole text at the time
+#[derive(Debug, PartialEq, Clone)]
+pub enum Entry {
+    Insert { offset: usize, text: Vec<u8> },
+    Delete { offset: usize, len: usize },
+}
+
+// every change made to a document since it was last saved, written to a
+// swap file next to it so the changes can be got back after a crash. The
+// file starts with a hash of the text the changes were made to and is only
+// created once there's something unsaved
+pub struct Journal {
+    path: PathBuf,
+    file: Option<File>,
+    // entries not written to the file yet, a whole batch goes at once
+    pending: Vec<u8>,
+    // a swap file left by an earlier run that nobody has recovered or
+    // discarded yet, nothing is written until then so it isn't lost
+    left_over: bool,
+}
+
+impl Journal {
+    // text is what the document holds now, a swap file written for other
+    // text can't be recovered so it doesn't count as left over
+    pub fn new(document: &Path, text: &[u8]) -> Journal {
+        let path = swap_path(document);
+        Journal {
+            left_over: is_swap_file_for(&path, text),
+            path,
+            file: None,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn left_over(&self) -> bool {
+        self.left_over
+    }
+
+    // the document was moved, its swap file goes with it
+    pub fn rename(&mut self, document: &Path) {
+        let path = swap_path(document);
+        if self.file.is_some() || self.left_over {
+            let _ = fs::rename(&self.path, &path);
+        }
+        self.path = path;
+    }
+
+    // text is what the document holds before the entry, only needed for
+    // the first one
+    pub fn record<F>(&mut self, entry: &Entry, text: F)
+    where
+        F: FnOnce() -> Vec<u8>,
+    {
+        if self.left_over {
+            return;
+        }
+        if self.file.is_none() && self.pending.is_empty() {
+            self.pending.extend_from_slice(MAGIC);
+            self.pending.extend_from_slice(&hash(&text()).to_le_bytes());
+        }
+        match entry {
+            Entry::Insert { offset, text } => {
+                self.pending.push(b'i');
+                self.pending
+                    .extend_from_slice(&(*offset as u64).to_le_bytes());
+                self.pending
+                    .extend_from_slice(&(text.len() as u64).to_le_bytes());
+                self.pending.extend_from_slice(text);
+            }
+            Entry::Delete { offset, len } => {
+                self.pending.push(b'd');
+                self.pending
+                    .extend_from_slice(&(*offset as u64).to_le_bytes());
+                self.pending.extend_from_slice(&(*len as u64).to_le_bytes());
+            }
+        }
+    }
+
+    // writes out what was recorded since the last flush
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&self.path)
+                .map_err(|e| format!("could not create {}: {}", self.path.display(), e))?;
+            self.file = Some(file);
+        }
+        let file = self.file.as_mut().unwrap();
+        file.write_all(&self.pending)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| format!("could not write {}: {}", self.path.display(), e))?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    // the changes in a swap file left over from an earlier run, as long as
+    // they were made to the text given. A last entry cut short by the crash
+    // is left out
+    pub fn recover(&mut self, text: &[u8]) -> Result<Vec<Entry>, String> {
+        let data = fs::read(&self.path).map_err(|e| e.to_string())?;
+        let Some(rest) = data.strip_prefix(MAGIC) else {
+            return Err(format!("{} isn't a swap file", self.path.display()));
+        };
+        if rest.len() < 8 || rest[..8] != hash(text).to_le_bytes() {
+            return Err("the file changed since the swap file was written".to_string());
+        }
+        let mut entries = Vec::new();
+        let mut rest = &rest[8..];
+        while rest.len() >= 17 {
+            let number = |at: usize| u64::from_le_bytes(rest[at..at + 8].try_into().unwrap());
+            let (offset, len) = (number(1) as usize, number(9) as usize);
+            let entry = match rest[0] {
+                b'i' if rest.len() >= 17 + len => Entry::Insert {
+                    offset,
+                    text: rest[17..17 + len].to_vec(),
+                },
+                b'd' => Entry::Delete { offset, len },
+                _ => break,
+            };
+            rest = match &entry {
+                Entry::Insert { .. } => &rest[17 + len..],
+                Entry::Delete { .. } => &rest[17..],
+            };
+            entries.push(entry);
+        }
+        // carry on adding to the same file, after the last whole entry
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&self.path)
+            .map_err(|e| e.to_string())?;
+        file.set_len((data.len() - rest.len()) as u64)
+            .and_then(|_| file.seek(SeekFrom::End(0)))
+            .map_err(|e| e.to_string())?;
+        self.file = Some(file);
+        self.left_over = false;
+        Ok(entries)
+    }
+
+    // moves a left over swap file out of the way to .name.text_editor-swp.old
+    // when it's neither recovered nor discarded, so the changes from now on
+    // can be journaled without losing it. Only the last one set aside is kept
+    pub fn set_aside(&mut self) -> Result<(), String> {
+        if !self.left_over {
+            return Ok(());
+        }
+        let mut old = self.path.clone().into_os_string();
+        old.push(".old");
+        fs::rename(&self.path, &old)
+            .map_err(|e| format!("could not move {}: {}", self.path.display(), e))?;
+        self.left_over = false;
+        Ok(())
+    }
+
+    // deletes the swap file, once the document is saved or the changes in
+    // it aren't wanted
+    pub fn remove(&mut self) {
+        if self.file.take().is_some() || self.left_over {
+            let _ = fs::remove_file(&self.path);
+        }
+        self.pending.clear();
+        self.left_over = false;
+    }
+}
+
+// .name.text_editor-swp in the same directory, not .name.swp so it isn't
+// taken for one of Vim's
+fn swap_path(document: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(document.file_name().unwrap_or_default());
+    name.push(".text_editor-swp");
+    document.with_file_name(name)
+}
+
+// whether the file is one of ours with changes made to the text given,
+// whatever else is there gets written over
+fn is_swap_file_for(path: &Path, text: &[u8]) -> bool {
+    let mut start = [0; MAGIC.len() + 8];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok_and(|_| {
+            start[..MAGIC.len()] == *MAGIC && start[MAGIC.len()..] == hash(text).to_le_bytes()
+        })
+}
+
+// FNV-1a, it has to come out the same from one build of the editor to the
+// next so a newer one can still recover
+pub fn hash(text: &[u8]) -> u64 {
+    text.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -1,10 +1,17 @@
-use crate::doubly_linked_list::List;
+use crate::{
+    doubly_linked_list::List,
+    history::{self, UndoTree},
+    swap::{Entry, Journal},
+};
 use std::{
-    fs::{self},
+    collections::hash_map::DefaultHasher,
+    fs::{self, Metadata},
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 struct Span {
     newlines: u32,
     is_append: bool,
@@ -12,82 +19,785 @@ struct Span {
     end_index: usize,
 }
 
+impl Span {
+    fn len(&self) -> usize {
+        self.end_index - self.start_index + 1
+    }
+}
+
+// the lines [start, end) had their contents changed by an edit, end is None
+// when lines were added or removed so everything from start onwards moved
+#[derive(Debug, PartialEq, Clone)]
+pub struct LineChange {
+    pub start: u32,
+    pub end: Option<u32>,
+    pub shift: Shift,
+}
+
+// the text from start to old_end became text from start to new_end, for
+// moving positions kept elsewhere along with the text they were on
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Shift {
+    pub start: (u32, u32),
+    pub old_end: (u32, u32),
+    pub new_end: (u32, u32),
+}
+
+impl Shift {
+    // a position at the start stays before inserted text, one inside deleted
+    // text ends up where it was deleted from
+    pub fn apply(&self, position: (u32, u32)) -> (u32, u32) {
+        if position <= self.start {
+            return position;
+        }
+        if position < self.old_end {
+            return self.start;
+        }
+        match position.0 == self.old_end.0 {
+            true => (self.new_end.0, self.new_end.1 + position.1 - self.old_end.1),
+            false => (position.0 + self.new_end.0 - self.old_end.0, position.1),
+        }
+    }
+}
+
+// what the file looked like when it was last read or written, to tell when
+// something else changes it
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct DiskStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl DiskStamp {
+    fn new(metadata: &Metadata, text: &[u8]) -> DiskStamp {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        DiskStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: hasher.finish(),
+        }
+    }
+
+    fn read(path: &Path, text: &[u8]) -> Option<DiskStamp> {
+        fs::metadata(path)
+            .ok()
+            .map(|metadata| DiskStamp::new(&metadata, text))
+    }
+}
+
+// one edit as it happened, offsets are into the whole text so it can be
+// undone and redone without knowing about lines
+#[derive(Debug, PartialEq, Clone)]
+pub enum Edit {
+    Insert { offset: usize, text: Vec<u8> },
+    Delete { offset: usize, text: Vec<u8> },
+}
+
 pub struct Buffer {
     file: PathBuf,
     original_text: Vec<u8>,
     append: Vec<u8>,
     spans: List<Span>,
+    changes: Vec<LineChange>,
+    // every state the text has been in, each node undoes as one step
+    undo_tree: UndoTree,
+    // edits made while a group is open go into open_group
+    group_depth: usize,
+    open_group: Vec<Edit>,
+    // stops undo and redo recording the edits they make
+    applying_history: bool,
+    // changed since it was opened or last saved
+    modified: bool,
+    // None for text that didn't come from a file
+    disk: Option<DiskStamp>,
+    // a change to the file that was found and not dealt with yet, so it
+    // isn't read again every time it's checked
+    disk_changed: Option<DiskStamp>,
+    // unsaved edits go into a swap file so a crash doesn't lose them, None
+    // for text that didn't come from a file
+    journal: Option<Journal>,
+    // where the undo history is kept between sessions, None to not keep it
+    history_dir: Option<PathBuf>,
+    // text with nowhere to be saved, file is only a name for it
+    scratch: bool,
 }
 
 impl Buffer {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
         let original_text = fs::read(path.as_ref()).map_err(|err| err.to_string())?;
-        let newlines = Self::num_newlines(&original_text);
+        let disk = DiskStamp::read(path.as_ref(), &original_text);
+        let journal = Journal::new(path.as_ref(), &original_text);
+        Ok(Buffer {
+            disk,
+            journal: Some(journal),
+            ..Self::from_text(PathBuf::from(path.as_ref()), original_text)
+        })
+    }
+
+    pub fn from_text(file: PathBuf, original_text: Vec<u8>) -> Self {
         let mut spans: List<Span> = List::new();
 
-        spans.push_front(Span {
-            newlines,
-            is_append: false,
-            start_index: 0,
-            end_index: original_text.len() - 1,
-        });
+        if !original_text.is_empty() {
+            spans.push_front(Span {
+                newlines: Self::num_newlines(&original_text),
+                is_append: false,
+                start_index: 0,
+                end_index: original_text.len() - 1,
+            });
+        }
 
-        Ok(Buffer {
-            file: PathBuf::from(path.as_ref()),
+        Buffer {
+            file,
             original_text,
             append: Vec::new(),
             spans,
-        })
+            changes: Vec::new(),
+            undo_tree: UndoTree::new(),
+            group_depth: 0,
+            open_group: Vec::new(),
+            applying_history: false,
+            modified: false,
+            disk: None,
+            disk_changed: None,
+            journal: None,
+            history_dir: None,
+            scratch: false,
+        }
     }
 
-    pub fn get_lines(&self, line_start: u32, line_end: u32) -> Vec<String> {
-        let mut newline_count: u32 = 0;
-        let mut line_buf: Vec<String> = Vec::new();
+    // text that isn't a file and can't be saved, like a diff. The name
+    // tells it apart from other buffers
+    pub fn scratch(name: PathBuf, text: Vec<u8>) -> Self {
+        Buffer {
+            scratch: true,
+            ..Self::from_text(name, text)
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        if self.scratch {
+            return Err("it isn't a file, there's nowhere to save it".to_string());
+        }
+        let text = self.get_text(0, self.text_len());
+        fs::write(&self.file, &text).map_err(|e| e.to_string())?;
+        self.modified = false;
+        self.disk = DiskStamp::read(&self.file, &text);
+        self.disk_changed = None;
+        if let Some(journal) = self.journal.as_mut() {
+            journal.remove();
+        }
+        // losing the history isn't worth failing the save over
+        if let Some(dir) = &self.history_dir {
+            let _ = history::save(dir, &self.file, &text, &self.undo_tree);
+        }
+        Ok(())
+    }
+
+    // picks up the undo history kept from an earlier session if the file
+    // hasn't changed since, and keeps it there from now on
+    pub fn restore_history(&mut self, dir: PathBuf) {
+        let text = self.get_text(0, self.text_len());
+        if let Some(undo_tree) = history::load(&dir, &self.file, &text) {
+            self.undo_tree = undo_tree;
+        }
+        self.history_dir = Some(dir);
+    }
 
-        let skip_spans = self
-            .spans
+    // whether something else wrote the file since it was opened, saved or
+    // reloaded. A new modification time with the same contents doesn't
+    // count, and a file that's gone is left for saving to bring back
+    pub fn changed_on_disk(&mut self) -> bool {
+        let Some(stamp) = self.disk else {
+            return false;
+        };
+        let Ok(metadata) = fs::metadata(&self.file) else {
+            return false;
+        };
+        let same_as = |stamp: DiskStamp| {
+            metadata.modified().ok() == stamp.modified && metadata.len() == stamp.len
+        };
+        if same_as(stamp) {
+            return false;
+        }
+        if self.disk_changed.is_some_and(same_as) {
+            return true;
+        }
+        let Ok(text) = fs::read(&self.file) else {
+            return false;
+        };
+        let current = DiskStamp::new(&metadata, &text);
+        if current.hash == stamp.hash {
+            self.disk = Some(current);
+            return false;
+        }
+        self.disk_changed = Some(current);
+        true
+    }
+
+    // takes in what's on disk now as one edit that can be undone, only the
+    // part that differs is replaced so lines around it stay put
+    pub fn reload(&mut self) -> Result<(), String> {
+        let text = fs::read(&self.file).map_err(|e| e.to_string())?;
+        let current = self.get_text(0, self.text_len());
+        let prefix = current
             .iter()
-            .take_while(|span| {
-                if newline_count + span.newlines < line_start {
-                    newline_count += span.newlines;
-                    return true;
-                }
-                false
-            })
+            .zip(&text)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = current[prefix..]
+            .iter()
+            .rev()
+            .zip(text[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
             .count();
+        self.begin_group();
+        self.delete_at(prefix, current.len() - prefix - suffix);
+        self.insert_at(prefix, &text[prefix..text.len() - suffix]);
+        self.end_group();
+        self.modified = false;
+        self.disk = DiskStamp::read(&self.file, &text);
+        self.disk_changed = None;
+        if let Some(journal) = self.journal.as_mut() {
+            journal.remove();
+        }
+        Ok(())
+    }
+
+    // keeps the text as it is, only later changes to the file count
+    pub fn ignore_disk_changes(&mut self) {
+        if let Ok(text) = fs::read(&self.file) {
+            self.disk = DiskStamp::read(&self.file, &text);
+            self.disk_changed = None;
+        }
+    }
+
+    // everything in the buffer
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.get_text(0, self.text_len())).into_owned()
+    }
 
-        let mut add_to_prev = false;
-        for span in self.spans.iter().skip(skip_spans) {
-            let char_span = self.get_character_span(span);
-            let string = String::from_utf8(char_span.to_vec())
-                .map_err(|e| e.to_string())
-                .unwrap();
-            let mut lines: Vec<String>;
-            if newline_count < line_start {
-                lines = string
-                    .split('\n')
-                    .skip((line_start - 1 - newline_count).try_into().unwrap())
-                    .map(|s| s.to_string())
-                    .collect();
-            } else {
-                lines = string.split('\n').map(|s| s.to_string()).collect();
-            }
-            // remove extra lines
-            lines.truncate((line_end - newline_count) as usize);
-            if add_to_prev {
-                let final_index = line_buf.len() - 1;
-                line_buf[final_index] = line_buf[line_buf.len() - 1].to_string() + &lines[0];
-                lines.remove(0);
-            }
-            add_to_prev = lines.len() > 0 && lines[lines.len() - 1] != "";
-            line_buf.append(&mut lines);
-            newline_count += lines.len() as u32;
-
-            if newline_count >= line_end {
+    // scratch text never counts, there's nothing to save it to
+    pub fn is_modified(&self) -> bool {
+        self.modified && !self.scratch
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.file
+    }
+
+    // the file was moved, saving writes to where it is now
+    pub fn set_path(&mut self, path: PathBuf) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.rename(&path);
+        }
+        self.file = path;
+    }
+
+    // there's a swap file from a run that didn't finish, waiting for
+    // recover or discard_swap. Edits aren't journaled until then
+    pub fn has_swap(&self) -> bool {
+        self.journal.as_ref().is_some_and(Journal::left_over)
+    }
+
+    // makes the edits in the swap file again, undoing as one step
+    pub fn recover(&mut self) -> Result<(), String> {
+        let Some(mut journal) = self.journal.take() else {
+            return Ok(());
+        };
+        let entries = journal.recover(&self.get_text(0, self.text_len()));
+        if let Ok(entries) = &entries {
+            self.begin_group();
+            for entry in entries {
+                match entry {
+                    Entry::Insert { offset, text } => self.insert_at(*offset, text),
+                    Entry::Delete { offset, len } => self.delete_at(*offset, *len),
+                }
+            }
+            self.end_group();
+        }
+        self.journal = Some(journal);
+        entries.map(|_| ())
+    }
+
+    // keeps the swap file without recovering it, see Journal::set_aside.
+    // When it can't be moved the buffer goes without a swap file so it
+    // isn't written over
+    pub fn set_swap_aside(&mut self) -> Result<(), String> {
+        let set_aside = match self.journal.as_mut() {
+            Some(journal) => journal.set_aside(),
+            None => Ok(()),
+        };
+        if set_aside.is_err() {
+            self.journal = None;
+        }
+        set_aside
+    }
+
+    pub fn discard_swap(&mut self) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.remove();
+        }
+    }
+
+    // writes the edits made since the last call to the swap file. Once that
+    // fails the buffer goes without one rather than failing on every edit
+    pub fn flush_journal(&mut self) -> Result<(), String> {
+        let flushed = match self.journal.as_mut() {
+            Some(journal) => journal.flush(),
+            None => Ok(()),
+        };
+        if flushed.is_err() {
+            self.journal = None;
+        }
+        flushed
+    }
+
+    // notes an edit in the swap file before it's made
+    fn journal(&mut self, entry: Entry) {
+        let Some(mut journal) = self.journal.take() else {
+            return;
+        };
+        journal.record(&entry, || self.get_text(0, self.text_len()));
+        self.journal = Some(journal);
+    }
+
+    pub fn get_lines(&self, line_start: u32, line_end: u32) -> Vec<String> {
+        let mut line_num: u32 = 0;
+        // lines are gathered as bytes and decoded whole, a character can be
+        // split across spans
+        let mut line_buf: Vec<Vec<u8>> = Vec::new();
+        if line_start == 0 && line_end > 0 {
+            line_buf.push(Vec::new());
+        }
+        'spans: for span in self.spans.iter() {
+            // skip spans that end before the first line we want
+            if line_num + span.newlines < line_start {
+                line_num += span.newlines;
+                continue;
+            }
+            for character in self.get_character_span(span) {
+                if *character == b'\n' {
+                    line_num += 1;
+                    if line_num >= line_end {
+                        break 'spans;
+                    }
+                    if line_num >= line_start {
+                        line_buf.push(Vec::new());
+                    }
+                } else if line_num >= line_start {
+                    line_buf.last_mut().unwrap().push(*character);
+                }
+            }
+        }
+        line_buf
+            .iter()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect()
+    }
+
+    pub fn line_count(&self) -> u32 {
+        self.spans.iter().map(|span| span.newlines).sum::<u32>() + 1
+    }
+
+    pub fn line_len(&self, line: u32) -> u32 {
+        self.get_lines(line, line + 1)
+            .first()
+            .map(|text| text.chars().count() as u32)
+            .unwrap_or(0)
+    }
+
+    // the text from start up to end, positions are (line, column)
+    pub fn get_range(&self, start: (u32, u32), end: (u32, u32)) -> String {
+        let from = self.offset_of(start.0, start.1);
+        let to = self.offset_of(end.0, end.1).max(from);
+        String::from_utf8_lossy(&self.get_text(from, to)).into_owned()
+    }
+
+    // how many bytes lie between two positions, newlines included, which is
+    // what delete takes
+    pub fn range_len(&self, start: (u32, u32), end: (u32, u32)) -> usize {
+        self.offset_of(end.0, end.1)
+            .saturating_sub(self.offset_of(start.0, start.1))
+    }
+
+    pub fn insert(&mut self, line: u32, column: u32, text: &str) {
+        let offset = self.offset_of(line, column);
+        self.insert_at(offset, text.as_bytes());
+    }
+
+    // deletes len bytes going forward from the position, joining lines when
+    // a newline is removed
+    pub fn delete(&mut self, line: u32, column: u32, len: usize) {
+        let start = self.offset_of(line, column);
+        self.delete_at(start, len);
+    }
+
+    fn insert_at(&mut self, offset: usize, text: &[u8]) {
+        if text.is_empty() {
+            return;
+        }
+        self.record(Edit::Insert {
+            offset,
+            text: text.to_vec(),
+        });
+        self.journal(Entry::Insert {
+            offset,
+            text: text.to_vec(),
+        });
+        self.modified = true;
+        let (line, column) = self.position_of(offset);
+        let newlines = Self::num_newlines(text);
+        let new_end = match text.iter().rposition(|byte| *byte == b'\n') {
+            Some(newline) => (
+                line + newlines,
+                Self::char_count(&text[newline + 1..]) as u32,
+            ),
+            None => (line, column + Self::char_count(text) as u32),
+        };
+        let start_index = self.append.len();
+        self.append.extend_from_slice(text);
+        let new_span = Span {
+            newlines,
+            is_append: true,
+            start_index,
+            end_index: self.append.len() - 1,
+        };
+        self.notify_change(Shift {
+            start: (line, column),
+            old_end: (line, column),
+            new_end,
+        });
+        self.insert_span(offset, new_span);
+    }
+
+    fn delete_at(&mut self, start: usize, len: usize) {
+        let end = (start + len).min(self.text_len());
+        if start >= end {
+            return;
+        }
+        self.journal(Entry::Delete {
+            offset: start,
+            len: end - start,
+        });
+        self.modified = true;
+        let removed = self.get_text(start, end);
+        let start_position = self.position_of(start);
+        self.notify_change(Shift {
+            start: start_position,
+            old_end: self.position_of(end),
+            new_end: start_position,
+        });
+        self.record(Edit::Delete {
+            offset: start,
+            text: removed,
+        });
+
+        let mut index = 0;
+        let mut span_start = 0;
+        while let Some(span) = self.spans.get_mut(index) {
+            let span_len = span.len();
+            let span_end = span_start + span_len;
+            if span_end <= start {
+                span_start = span_end;
+                index += 1;
+                continue;
+            }
+            if span_start >= end {
                 break;
             }
+            // keep whatever of the span falls outside the deleted range
+            let keep_left = start.saturating_sub(span_start);
+            let keep_right = span_end.saturating_sub(end);
+            let span = self.spans.remove(index).unwrap();
+            if keep_left > 0 {
+                self.spans.insert(index, self.sub_span(&span, 0, keep_left));
+                index += 1;
+            }
+            if keep_right > 0 {
+                let right = self.sub_span(&span, span_len - keep_right, span_len);
+                self.spans.insert(index, right);
+                index += 1;
+            }
+            span_start = span_end;
+        }
+    }
+
+    // edits made until the matching end_group undo as one step, groups can nest
+    pub fn begin_group(&mut self) {
+        self.group_depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth == 0 && !self.open_group.is_empty() {
+            let group = std::mem::take(&mut self.open_group);
+            self.undo_tree.push(group);
+        }
+    }
+
+    fn record(&mut self, edit: Edit) {
+        if self.applying_history {
+            return;
+        }
+        if self.group_depth > 0 {
+            self.open_group.push(edit);
+        } else {
+            self.undo_tree.push(vec![edit]);
+        }
+    }
+
+    pub fn undo_tree(&self) -> &UndoTree {
+        &self.undo_tree
+    }
+
+    // reverts the last group of edits and returns where the first of them was
+    pub fn undo(&mut self) -> Option<(u32, u32)> {
+        let node = self.undo_tree.undo()?;
+        Some(self.apply_node(node, false))
+    }
+
+    // makes the last undone group again and returns where its last edit ended
+    pub fn redo(&mut self) -> Option<(u32, u32)> {
+        let node = self.undo_tree.redo()?;
+        Some(self.apply_node(node, true))
+    }
+
+    // goes through the states the text has been in the order they happened,
+    // whichever branch they're on, negative steps going back. Returns where
+    // the last edit on the way was
+    pub fn travel(&mut self, steps: i32) -> Option<(u32, u32)> {
+        let last = self.undo_tree.nodes().len() as i64 - 1;
+        let current = self.undo_tree.current();
+        let target = (current as i64 + steps as i64).clamp(0, last) as usize;
+        if target == current {
+            return None;
+        }
+        let mut position = None;
+        for (node, forward) in self.undo_tree.go_to(target) {
+            position = Some(self.apply_node(node, forward));
+        }
+        position
+    }
+
+    // makes a node's edits, or reverts them, and returns where the cursor
+    // goes after
+    fn apply_node(&mut self, node: usize, forward: bool) -> (u32, u32) {
+        let edits = self.undo_tree.nodes()[node].edits.clone();
+        self.applying_history = true;
+        if forward {
+            for edit in &edits {
+                match edit {
+                    Edit::Insert { offset, text } => self.insert_at(*offset, text),
+                    Edit::Delete { offset, text } => self.delete_at(*offset, text.len()),
+                }
+            }
+        } else {
+            for edit in edits.iter().rev() {
+                match edit {
+                    Edit::Insert { offset, text } => self.delete_at(*offset, text.len()),
+                    Edit::Delete { offset, text } => self.insert_at(*offset, text),
+                }
+            }
+        }
+        self.applying_history = false;
+        match (forward, edits.last().unwrap(), &edits[0]) {
+            (true, Edit::Insert { offset, text }, _) => self.position_of(offset + text.len()),
+            (true, Edit::Delete { offset, .. }, _) => self.position_of(*offset),
+            (false, _, Edit::Insert { offset, .. }) => self.position_of(*offset),
+            (false, _, Edit::Delete { offset, text }) => self.position_of(offset + text.len()),
         }
-        return line_buf;
+    }
+
+    // the offset of the next match of needle at or after from, carrying on
+    // from the start once the end is reached
+    pub fn find(&self, needle: &str, from: usize) -> Option<usize> {
+        let needle = needle.as_bytes();
+        if needle.is_empty() {
+            return None;
+        }
+        let text = self.get_text(0, self.text_len());
+        let found = |start: usize, end: usize| {
+            text.get(start..end.min(text.len()))?
+                .windows(needle.len())
+                .position(|window| window == needle)
+                .map(|index| start + index)
+        };
+        found(from, text.len()).or_else(|| found(0, from + needle.len() - 1))
+    }
+
+    // everything that changed since the last call, used to work out what needs redrawing
+    pub fn take_changes(&mut self) -> Vec<LineChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    fn notify_change(&mut self, shift: Shift) {
+        let line = shift.start.0;
+        let same_lines = shift.old_end.0 == line && shift.new_end.0 == line;
+        self.changes.push(LineChange {
+            start: line,
+            end: same_lines.then_some(line + 1),
+            shift,
+        });
+    }
+
+    fn insert_span(&mut self, offset: usize, new_span: Span) {
+        let mut index = 0;
+        let mut span_start = 0;
+        let mut split_at = None;
+        for span in self.spans.iter() {
+            if offset < span_start + span.len() {
+                if offset > span_start {
+                    split_at = Some(offset - span_start);
+                }
+                break;
+            }
+            span_start += span.len();
+            index += 1;
+        }
+
+        match split_at {
+            Some(split_at) => {
+                let span = self.spans.remove(index).unwrap();
+                let left = self.sub_span(&span, 0, split_at);
+                let right = self.sub_span(&span, split_at, span.len());
+                self.spans.insert(index, right);
+                self.spans.insert(index, new_span);
+                self.spans.insert(index, left);
+            }
+            None => {
+                // typing one character after another keeps extending the same span
+                if index > 0 {
+                    if let Some(previous) = self.spans.get_mut(index - 1) {
+                        if previous.is_append && previous.end_index + 1 == new_span.start_index {
+                            previous.end_index = new_span.end_index;
+                            previous.newlines += new_span.newlines;
+                            return;
+                        }
+                    }
+                }
+                self.spans.insert(index, new_span);
+            }
+        }
+    }
+
+    // the part of a span covering its characters [from, to)
+    fn sub_span(&self, span: &Span, from: usize, to: usize) -> Span {
+        let mut sub_span = Span {
+            newlines: 0,
+            is_append: span.is_append,
+            start_index: span.start_index + from,
+            end_index: span.start_index + to - 1,
+        };
+        sub_span.newlines = Self::num_newlines(self.get_character_span(&sub_span));
+        sub_span
+    }
+
+    // the (line, column) an offset into the whole text falls on
+    pub fn position_of(&self, offset: usize) -> (u32, u32) {
+        let offset = offset.min(self.text_len());
+        let mut line = 0;
+        let mut line_start = 0;
+        let mut span_start = 0;
+        for span in self.spans.iter() {
+            if span_start >= offset {
+                break;
+            }
+            let characters = self.get_character_span(span);
+            let take = (offset - span_start).min(characters.len());
+            if take == characters.len() && span.newlines == 0 {
+                span_start += characters.len();
+                continue;
+            }
+            for (index, character) in characters[..take].iter().enumerate() {
+                if *character == b'\n' {
+                    line += 1;
+                    line_start = span_start + index + 1;
+                }
+            }
+            span_start += characters.len();
+        }
+        let column = Self::char_count(&self.get_text(line_start, offset));
+        (line, column as u32)
+    }
+
+    pub fn offset_of(&self, line: u32, column: u32) -> usize {
+        let mut offset = 0;
+        let mut line_num = 0;
+        if line > 0 {
+            'spans: for span in self.spans.iter() {
+                if line_num + span.newlines < line {
+                    line_num += span.newlines;
+                    offset += span.len();
+                    continue;
+                }
+                for (index, character) in self.get_character_span(span).iter().enumerate() {
+                    if *character == b'\n' {
+                        line_num += 1;
+                        if line_num == line {
+                            offset += index + 1;
+                            break 'spans;
+                        }
+                    }
+                }
+                offset += span.len();
+            }
+        }
+        self.char_offset(offset, column as usize)
+    }
+
+    // the offset chars characters on from an offset, the end of the text if
+    // it's closer. Columns count characters while offsets count bytes
+    fn char_offset(&self, from: usize, chars: usize) -> usize {
+        let mut seen = 0;
+        let mut span_start = 0;
+        for span in self.spans.iter() {
+            let characters = self.get_character_span(span);
+            let span_end = span_start + characters.len();
+            if span_end > from {
+                let skip = from.saturating_sub(span_start);
+                for (index, byte) in characters.iter().enumerate().skip(skip) {
+                    if Self::starts_char(*byte) {
+                        if seen == chars {
+                            return span_start + index;
+                        }
+                        seen += 1;
+                    }
+                }
+            }
+            span_start = span_end;
+        }
+        self.text_len()
+    }
+
+    fn char_count(text: &[u8]) -> usize {
+        text.iter().filter(|byte| Self::starts_char(**byte)).count()
+    }
+
+    // every byte but the continuation bytes of UTF-8
+    fn starts_char(byte: u8) -> bool {
+        byte & 0xc0 != 0x80
+    }
+
+    fn get_text(&self, start: usize, end: usize) -> Vec<u8> {
+        let mut text = Vec::with_capacity(end - start);
+        let mut span_start = 0;
+        for span in self.spans.iter() {
+            let span_end = span_start + span.len();
+            if span_end > start && span_start < end {
+                let characters = self.get_character_span(span);
+                let from = start.saturating_sub(span_start);
+                let to = (end - span_start).min(span.len());
+                text.extend_from_slice(&characters[from..to]);
+            }
+            span_start = span_end;
+        }
+        text
+    }
+
+    fn text_len(&self) -> usize {
+        self.spans.iter().map(|span| span.len()).sum()
     }
 
     fn num_newlines(buf: &[u8]) -> u32 {
@@ -105,8 +815,332 @@ impl Buffer {
             &self.original_text[span.start_index..=span.end_index]
         }
     }
+}
 
-    pub fn redo(&mut self) {}
+#[cfg(test)]
+mod test {
+    use super::{Buffer, LineChange, Shift};
+    use std::path::PathBuf;
+
+    fn buffer(text: &str) -> Buffer {
+        Buffer::from_text(PathBuf::from("test.txt"), text.as_bytes().to_vec())
+    }
 
-    pub fn undo(&mut self) {}
+    fn contents(buffer: &Buffer) -> String {
+        buffer.get_lines(0, u32::MAX).join("\n")
+    }
+
+    #[test]
+    fn get_lines() {
+        let buffer = buffer("one\ntwo\nthree\n");
+        assert_eq!(buffer.line_count(), 4);
+        assert_eq!(buffer.get_lines(0, 2), vec!["one", "two"]);
+        assert_eq!(buffer.get_lines(1, 10), vec!["two", "three", ""]);
+        assert_eq!(buffer.line_len(2), 5);
+    }
+
+    #[test]
+    fn empty_file() {
+        let mut buffer = buffer("");
+        assert_eq!(buffer.get_lines(0, 10), vec![""]);
+        buffer.insert(0, 0, "hi");
+        assert_eq!(contents(&buffer), "hi");
+    }
+
+    #[test]
+    fn insert() {
+        let mut buffer = buffer("hello world\nsecond");
+        buffer.insert(0, 5, ",");
+        buffer.insert(0, 6, "!");
+        buffer.insert(1, 6, "\nthird");
+        buffer.insert(0, 0, ">");
+        assert_eq!(contents(&buffer), ">hello,! world\nsecond\nthird");
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(
+            buffer.take_changes(),
+            vec![
+                LineChange {
+                    start: 0,
+                    end: Some(1),
+                    shift: Shift {
+                        start: (0, 5),
+                        old_end: (0, 5),
+                        new_end: (0, 6)
+                    }
+                },
+                LineChange {
+                    start: 0,
+                    end: Some(1),
+                    shift: Shift {
+                        start: (0, 6),
+                        old_end: (0, 6),
+                        new_end: (0, 7)
+                    }
+                },
+                LineChange {
+                    start: 1,
+                    end: None,
+                    shift: Shift {
+                        start: (1, 6),
+                        old_end: (1, 6),
+                        new_end: (2, 5)
+                    }
+                },
+                LineChange {
+                    start: 0,
+                    end: Some(1),
+                    shift: Shift {
+                        start: (0, 0),
+                        old_end: (0, 0),
+                        new_end: (0, 1)
+                    }
+                },
+            ]
+        );
+        assert!(buffer.take_changes().is_empty());
+    }
+
+    // what another pane showing the buffer does with its cursors
+    #[test]
+    fn shifting_positions() {
+        let mut buffer = buffer("one\ntwo\nthree");
+        let moved = |buffer: &mut Buffer, positions: &[(u32, u32)]| {
+            let changes = buffer.take_changes();
+            positions
+                .iter()
+                .map(|position| {
+                    changes
+                        .iter()
+                        .fold(*position, |position, change| change.shift.apply(position))
+                })
+                .collect::<Vec<(u32, u32)>>()
+        };
+        buffer.insert(1, 1, "ne\nw");
+        assert_eq!(
+            moved(&mut buffer, &[(0, 2), (1, 1), (1, 2), (2, 3)]),
+            vec![(0, 2), (1, 1), (2, 2), (3, 3)]
+        );
+        assert_eq!(contents(&buffer), "one\ntne\nwwo\nthree");
+        buffer.delete(0, 2, 6);
+        assert_eq!(
+            moved(&mut buffer, &[(0, 1), (1, 1), (2, 2), (3, 3)]),
+            vec![(0, 1), (0, 2), (0, 4), (1, 3)]
+        );
+        assert_eq!(contents(&buffer), "onwwo\nthree");
+    }
+
+    #[test]
+    fn ranges() {
+        let buffer = buffer("one\ntwo\nthree");
+        assert_eq!(buffer.get_range((0, 1), (2, 2)), "ne\ntwo\nth");
+        assert_eq!(buffer.range_len((0, 1), (2, 2)), 9);
+        assert_eq!(buffer.get_range((1, 3), (1, 0)), "");
+
+        // columns count characters, offsets count bytes
+        let buffer = self::buffer("caf\u{e9} \u{1f600}!\nn\u{e9}");
+        assert_eq!(buffer.line_len(0), 7);
+        assert_eq!(buffer.get_lines(0, 1), vec!["caf\u{e9} \u{1f600}!"]);
+        assert_eq!(buffer.get_range((0, 3), (0, 6)), "\u{e9} \u{1f600}");
+        assert_eq!(buffer.range_len((0, 3), (0, 6)), 7);
+        assert_eq!(buffer.offset_of(1, 2), 15);
+        assert_eq!(buffer.position_of(10), (0, 6));
+    }
+
+    #[test]
+    fn positions() {
+        let mut buffer = buffer("one\ntwo");
+        buffer.insert(1, 0, "\n");
+        assert_eq!(buffer.position_of(0), (0, 0));
+        assert_eq!(buffer.position_of(3), (0, 3));
+        assert_eq!(buffer.position_of(4), (1, 0));
+        assert_eq!(buffer.position_of(6), (2, 1));
+        assert_eq!(buffer.position_of(100), (2, 3));
+        assert_eq!(buffer.find("o", 3), Some(7));
+        assert_eq!(buffer.find("on", 3), Some(0));
+        assert_eq!(buffer.find("x", 0), None);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut buffer = buffer("hello world");
+        assert!(!buffer.is_modified());
+        buffer.insert(0, 5, ",");
+        assert!(buffer.is_modified());
+        buffer.begin_group();
+        buffer.delete(0, 0, 1);
+        buffer.insert(0, 0, "J");
+        buffer.end_group();
+        assert_eq!(contents(&buffer), "Jello, world");
+        assert_eq!(buffer.undo(), Some((0, 1)));
+        assert_eq!(contents(&buffer), "hello, world");
+        assert_eq!(buffer.undo(), Some((0, 5)));
+        assert_eq!(contents(&buffer), "hello world");
+        assert_eq!(buffer.undo(), None);
+        assert_eq!(buffer.redo(), Some((0, 6)));
+        assert_eq!(buffer.redo(), Some((0, 1)));
+        assert_eq!(contents(&buffer), "Jello, world");
+        buffer.undo();
+        buffer.insert(0, 0, ">");
+        assert_eq!(buffer.redo(), None);
+        assert_eq!(contents(&buffer), ">hello, world");
+        // the undone edit is still there on its own branch
+        assert_eq!(buffer.travel(-1), Some((0, 1)));
+        assert_eq!(contents(&buffer), "Jello, world");
+        assert_eq!(buffer.travel(-3), Some((0, 5)));
+        assert_eq!(contents(&buffer), "hello world");
+        assert_eq!(buffer.travel(-1), None);
+        assert_eq!(buffer.travel(10), Some((0, 1)));
+        assert_eq!(contents(&buffer), ">hello, world");
+    }
+
+    #[test]
+    fn delete() {
+        let mut buffer = buffer("hello world\nsecond");
+        buffer.insert(0, 5, " there");
+        buffer.delete(0, 3, 6);
+        assert_eq!(contents(&buffer), "helre world\nsecond");
+        buffer.delete(0, 11, 1);
+        assert_eq!(contents(&buffer), "helre worldsecond");
+        assert_eq!(buffer.line_count(), 1);
+        buffer.delete(0, 0, 100);
+        assert_eq!(contents(&buffer), "");
+    }
+
+    #[test]
+    fn external_changes() {
+        let path = std::env::temp_dir().join(format!("text_buffer_test_{}", std::process::id()));
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        assert!(!buffer.changed_on_disk());
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        assert!(!buffer.changed_on_disk());
+
+        std::fs::write(&path, "one\n2\nthree\nfour\n").unwrap();
+        assert!(buffer.changed_on_disk());
+        buffer.reload().unwrap();
+        assert_eq!(contents(&buffer), "one\n2\nthree\nfour\n");
+        assert!(!buffer.is_modified());
+        assert!(!buffer.changed_on_disk());
+        buffer.undo();
+        assert_eq!(buffer.text(), "one\ntwo\nthree\n");
+
+        std::fs::write(&path, "changed").unwrap();
+        assert!(buffer.changed_on_disk());
+        buffer.ignore_disk_changes();
+        assert!(!buffer.changed_on_disk());
+        std::fs::remove_file(&path).unwrap();
+        assert!(!buffer.changed_on_disk());
+    }
+
+    #[test]
+    fn crash_recovery() {
+        let dir = std::env::temp_dir().join(format!("swap_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        assert!(!buffer.has_swap());
+        buffer.insert(1, 0, "one and a half\n");
+        buffer.delete(0, 0, 4);
+        buffer.flush_journal().unwrap();
+        assert!(dir.join(".notes.txt.text_editor-swp").exists());
+        // never saved, as if the editor crashed
+        drop(buffer);
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        assert!(buffer.has_swap());
+        buffer.recover().unwrap();
+        assert_eq!(buffer.text(), "one and a half\ntwo\n");
+        assert!(buffer.is_modified());
+        buffer.insert(0, 0, ">");
+        buffer.flush_journal().unwrap();
+        drop(buffer);
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.recover().unwrap();
+        assert_eq!(buffer.text(), ">one and a half\ntwo\n");
+        buffer.save().unwrap();
+        assert!(!dir.join(".notes.txt.text_editor-swp").exists());
+
+        // one set aside is kept and journaling starts over
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.insert(0, 0, "kept");
+        buffer.flush_journal().unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.set_swap_aside().unwrap();
+        assert!(!buffer.has_swap());
+        assert!(dir.join(".notes.txt.text_editor-swp.old").exists());
+        buffer.insert(0, 0, "new");
+        buffer.flush_journal().unwrap();
+        assert!(dir.join(".notes.txt.text_editor-swp").exists());
+        buffer.discard_swap();
+
+        // something else that happens to be there isn't offered
+        std::fs::write(dir.join(".notes.txt.text_editor-swp"), "not ours").unwrap();
+        assert!(!Buffer::open(&path).unwrap().has_swap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // a swap file whose changes were made to other text isn't offered, and
+    // one that stops matching before it's recovered gets set aside
+    #[test]
+    fn swap_for_other_text() {
+        let dir = std::env::temp_dir().join(format!("swap_mismatch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "one\n").unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.insert(0, 0, "lost");
+        buffer.flush_journal().unwrap();
+        let mut waiting = Buffer::open(&path).unwrap();
+        assert!(waiting.has_swap());
+
+        std::fs::write(&path, "changed elsewhere").unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        assert!(!buffer.has_swap());
+        buffer.insert(0, 0, ">");
+        buffer.flush_journal().unwrap();
+
+        assert!(waiting.recover().is_err());
+        assert_eq!(waiting.text(), "one\n");
+        waiting.set_swap_aside().unwrap();
+        assert!(!waiting.has_swap());
+        assert!(dir.join(".notes.txt.text_editor-swp.old").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn history_between_sessions() {
+        let dir = std::env::temp_dir().join(format!("history_test_{}", std::process::id()));
+        let cache = dir.join("cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "one\n").unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.restore_history(cache.clone());
+        buffer.insert(1, 0, "two\n");
+        buffer.begin_group();
+        buffer.insert(2, 0, "three\n");
+        buffer.delete(0, 0, 4);
+        buffer.end_group();
+        buffer.undo();
+        buffer.save().unwrap();
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.restore_history(cache.clone());
+        assert_eq!(buffer.text(), "one\ntwo\n");
+        buffer.redo();
+        assert_eq!(buffer.text(), "two\nthree\n");
+        buffer.undo();
+        buffer.undo();
+        assert_eq!(buffer.text(), "one\n");
+        assert_eq!(buffer.undo(), None);
+
+        // a file changed since doesn't get a history that doesn't fit it
+        std::fs::write(&path, "something else\n").unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.restore_history(cache);
+        assert_eq!(buffer.undo(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
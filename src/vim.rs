@@ -0,0 +1,870 @@
+use crate::{
+    motion::{self, Class},
+    text_buffer::Buffer,
+};
+
+// (line, column) in the buffer
+pub type Pos = (u32, u32);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+// what the editor hands over, printable characters come from text input and
+// the rest from the keys that don't type anything
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Key {
+    Char(char),
+    Escape,
+    Backspace,
+    Enter,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Motion {
+    Left,
+    Right,
+    Down,
+    Up,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    LineStart,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    Find(char),
+}
+
+// how much of the text between the cursor and where a motion lands an
+// operator covers
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Extent {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Command {
+    Move(Motion),
+    // no motion when the operator was doubled, dd, or in visual mode where it
+    // works on the selection
+    Operate(Operator, Option<Motion>),
+    // i, a, I, A, o or O
+    Insert(char),
+    DeleteChar,
+    // P puts before the cursor
+    Put { before: bool },
+    Visual,
+    Repeat,
+    // g- and g+ go back and forth through the undo history in the order it
+    // happened
+    Travel { forward: bool },
+}
+
+#[derive(Debug, PartialEq)]
+enum Parse {
+    Incomplete,
+    Invalid,
+    Done(Option<usize>, Command),
+}
+
+pub struct Vim {
+    mode: Mode,
+    // keys of the normal mode command typed so far
+    pending: Vec<Key>,
+    visual_anchor: Pos,
+    register: String,
+    register_linewise: bool,
+    // the keys of the last change so . can play them again, a change that
+    // enters insert mode keeps recording until escape
+    last_change: Vec<Key>,
+    recording: Option<Vec<Key>>,
+    replaying: bool,
+    readonly: bool,
+    // characters that count as part of a word on top of letters and digits
+    word_chars: String,
+}
+
+impl Vim {
+    pub fn new(readonly: bool, word_chars: &str) -> Vim {
+        Vim {
+            mode: Mode::Normal,
+            pending: Vec::new(),
+            visual_anchor: (0, 0),
+            register: String::new(),
+            register_linewise: false,
+            last_change: Vec::new(),
+            recording: None,
+            replaying: false,
+            readonly,
+            word_chars: word_chars.to_string(),
+        }
+    }
+
+    pub fn set_word_chars(&mut self, word_chars: &str) {
+        self.word_chars = word_chars.to_string();
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn indicator(&self) -> String {
+        match self.mode {
+            Mode::Insert => "-- INSERT --".to_string(),
+            Mode::Visual => "-- VISUAL --".to_string(),
+            Mode::Normal => {
+                let pending: String = self
+                    .pending
+                    .iter()
+                    .filter_map(|key| match key {
+                        Key::Char(c) => Some(*c),
+                        _ => None,
+                    })
+                    .collect();
+                format!("NORMAL {pending}").trim_end().to_string()
+            }
+        }
+    }
+
+    // the characters covered by the visual selection, end exclusive
+    pub fn selection(&self, cursor: Pos) -> Option<(Pos, Pos)> {
+        if self.mode != Mode::Visual {
+            return None;
+        }
+        let (start, end) = order(self.visual_anchor, cursor);
+        Some((start, (end.0, end.1 + 1)))
+    }
+
+    // feeds one key in and returns where the cursor ends up
+    pub fn key(&mut self, key: Key, buffer: &mut Buffer, cursor: Pos) -> Pos {
+        if self.mode == Mode::Insert {
+            return self.insert_key(key, buffer, cursor);
+        }
+        if key == Key::Escape {
+            self.pending.clear();
+            self.mode = Mode::Normal;
+            return clamp_normal(buffer, cursor);
+        }
+        self.pending.push(key);
+        let (count, command) = match parse(&self.pending, self.mode) {
+            Parse::Incomplete => return cursor,
+            Parse::Invalid => {
+                self.pending.clear();
+                return cursor;
+            }
+            Parse::Done(count, command) => (count, command),
+        };
+        let keys = std::mem::take(&mut self.pending);
+        let changes = !matches!(
+            command,
+            Command::Move(_) | Command::Visual | Command::Operate(Operator::Yank, _)
+        );
+        if changes && self.readonly {
+            return cursor;
+        }
+
+        let cursor = self.run(count, command, buffer, cursor);
+        if changes
+            && !matches!(command, Command::Repeat | Command::Travel { .. })
+            && !self.replaying
+        {
+            if self.mode == Mode::Insert {
+                self.recording = Some(keys);
+            } else if self.mode == Mode::Normal {
+                self.last_change = keys;
+            }
+        }
+        match self.mode {
+            Mode::Insert => cursor,
+            _ => clamp_normal(buffer, cursor),
+        }
+    }
+
+    fn run(
+        &mut self,
+        count: Option<usize>,
+        command: Command,
+        buffer: &mut Buffer,
+        cursor: Pos,
+    ) -> Pos {
+        match command {
+            Command::Move(motion) => {
+                match motion_target(buffer, cursor, motion, count, false, &self.word_chars) {
+                    Some((target, _)) => target,
+                    None => cursor,
+                }
+            }
+            Command::Operate(operator, None) if self.mode == Mode::Visual => {
+                self.mode = Mode::Normal;
+                let (start, end) = order(self.visual_anchor, cursor);
+                self.operate(operator, buffer, start, end, Extent::Inclusive)
+            }
+            Command::Operate(operator, None) => {
+                let last = (cursor.0 as usize + count.unwrap_or(1) - 1)
+                    .min(buffer.line_count() as usize - 1) as u32;
+                self.operate(operator, buffer, cursor, (last, cursor.1), Extent::Linewise)
+            }
+            Command::Operate(operator, Some(motion)) => {
+                // cw changes to the end of the word like ce
+                let target = match (operator, motion) {
+                    (Operator::Change, Motion::WordForward)
+                        if class_at(buffer, cursor, &self.word_chars) != Class::Space =>
+                    {
+                        Some((
+                            change_word_end(buffer, cursor, count.unwrap_or(1), &self.word_chars),
+                            Extent::Inclusive,
+                        ))
+                    }
+                    _ => motion_target(buffer, cursor, motion, count, true, &self.word_chars),
+                };
+                match target {
+                    Some((target, extent)) => {
+                        self.operate(operator, buffer, cursor, target, extent)
+                    }
+                    None => cursor,
+                }
+            }
+            Command::DeleteChar if self.mode == Mode::Visual => self.run(
+                None,
+                Command::Operate(Operator::Delete, None),
+                buffer,
+                cursor,
+            ),
+            Command::DeleteChar => {
+                let end = (cursor.1 + count.unwrap_or(1) as u32).min(buffer.line_len(cursor.0));
+                self.operate(
+                    Operator::Delete,
+                    buffer,
+                    cursor,
+                    (cursor.0, end),
+                    Extent::Exclusive,
+                )
+            }
+            Command::Insert(kind) => {
+                self.mode = Mode::Insert;
+                let (line, column) = cursor;
+                let len = buffer.line_len(line);
+                match kind {
+                    'a' => (line, (column + 1).min(len)),
+                    'I' => (line, first_non_blank(buffer, line)),
+                    'A' => (line, len),
+                    'o' => {
+                        buffer.insert(line, len, "\n");
+                        (line + 1, 0)
+                    }
+                    'O' => {
+                        buffer.insert(line, 0, "\n");
+                        (line, 0)
+                    }
+                    _ => cursor,
+                }
+            }
+            Command::Put { before } => self.put(buffer, cursor, before, count.unwrap_or(1)),
+            Command::Visual => {
+                match self.mode {
+                    Mode::Visual => self.mode = Mode::Normal,
+                    _ => {
+                        self.mode = Mode::Visual;
+                        self.visual_anchor = cursor;
+                    }
+                }
+                cursor
+            }
+            Command::Travel { forward } => {
+                let steps = count.unwrap_or(1) as i32;
+                buffer
+                    .travel(if forward { steps } else { -steps })
+                    .unwrap_or(cursor)
+            }
+            Command::Repeat => {
+                if self.replaying {
+                    return cursor;
+                }
+                self.replaying = true;
+                let mut cursor = cursor;
+                for key in self.last_change.clone() {
+                    cursor = self.key(key, buffer, cursor);
+                }
+                self.replaying = false;
+                cursor
+            }
+        }
+    }
+
+    fn insert_key(&mut self, key: Key, buffer: &mut Buffer, cursor: Pos) -> Pos {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(key);
+        }
+        let (line, column) = cursor;
+        match key {
+            Key::Char(c) => {
+                let text = c.to_string();
+                buffer.insert(line, column, &text);
+                advance(cursor, &text)
+            }
+            Key::Enter => {
+                buffer.insert(line, column, "\n");
+                (line + 1, 0)
+            }
+            Key::Backspace => {
+                if column > 0 {
+                    let len = buffer.range_len((line, column - 1), cursor);
+                    buffer.delete(line, column - 1, len);
+                    (line, column - 1)
+                } else if line > 0 {
+                    let previous_len = buffer.line_len(line - 1);
+                    buffer.delete(line - 1, previous_len, 1);
+                    (line - 1, previous_len)
+                } else {
+                    cursor
+                }
+            }
+            Key::Escape => {
+                self.mode = Mode::Normal;
+                if let Some(recording) = self.recording.take() {
+                    self.last_change = recording;
+                }
+                clamp_normal(buffer, (line, column.saturating_sub(1)))
+            }
+        }
+    }
+
+    // runs an operator over the text between from and to, returns the cursor
+    fn operate(
+        &mut self,
+        operator: Operator,
+        buffer: &mut Buffer,
+        from: Pos,
+        to: Pos,
+        extent: Extent,
+    ) -> Pos {
+        let (start, end) = order(from, to);
+        if extent == Extent::Linewise {
+            return self.operate_lines(operator, buffer, start.0, end.0, from);
+        }
+        let mut end = match extent {
+            Extent::Inclusive => (end.0, (end.1 + 1).min(buffer.line_len(end.0))),
+            _ => end,
+        };
+        // an exclusive motion landing at the start of a line stops at the end
+        // of the line before
+        if extent == Extent::Exclusive && end.1 == 0 && end.0 > start.0 {
+            end = (end.0 - 1, buffer.line_len(end.0 - 1));
+        }
+        self.register = buffer.get_range(start, end);
+        self.register_linewise = false;
+        match operator {
+            Operator::Yank => start,
+            Operator::Delete | Operator::Change => {
+                buffer.delete(start.0, start.1, buffer.range_len(start, end));
+                if operator == Operator::Change {
+                    self.mode = Mode::Insert;
+                }
+                start
+            }
+        }
+    }
+
+    fn operate_lines(
+        &mut self,
+        operator: Operator,
+        buffer: &mut Buffer,
+        first: u32,
+        last: u32,
+        cursor: Pos,
+    ) -> Pos {
+        let last_len = buffer.line_len(last);
+        self.register = buffer.get_range((first, 0), (last, last_len)) + "\n";
+        self.register_linewise = true;
+        match operator {
+            Operator::Yank => (first, cursor.1),
+            Operator::Change => {
+                buffer.delete(first, 0, buffer.range_len((first, 0), (last, last_len)));
+                self.mode = Mode::Insert;
+                (first, 0)
+            }
+            Operator::Delete => {
+                if last + 1 < buffer.line_count() {
+                    buffer.delete(first, 0, buffer.range_len((first, 0), (last + 1, 0)));
+                } else if first > 0 {
+                    // the last line has no newline after it, take the one before
+                    let start = (first - 1, buffer.line_len(first - 1));
+                    buffer.delete(start.0, start.1, buffer.range_len(start, (last, last_len)));
+                } else {
+                    buffer.delete(0, 0, buffer.range_len((0, 0), (last, last_len)));
+                }
+                let line = first.min(buffer.line_count() - 1);
+                (line, first_non_blank(buffer, line))
+            }
+        }
+    }
+
+    fn put(&mut self, buffer: &mut Buffer, cursor: Pos, before: bool, count: usize) -> Pos {
+        if self.register.is_empty() {
+            return cursor;
+        }
+        let text = self.register.repeat(count);
+        let (line, column) = cursor;
+        if self.register_linewise {
+            let line = match before {
+                true => {
+                    buffer.insert(line, 0, &text);
+                    line
+                }
+                false if line + 1 < buffer.line_count() => {
+                    buffer.insert(line + 1, 0, &text);
+                    line + 1
+                }
+                false => {
+                    // no line after to put it in front of
+                    let text = format!("\n{}", text.strip_suffix('\n').unwrap_or(&text));
+                    buffer.insert(line, buffer.line_len(line), &text);
+                    line + 1
+                }
+            };
+            return (line, first_non_blank(buffer, line));
+        }
+        let at = match before {
+            true => cursor,
+            false => (line, (column + 1).min(buffer.line_len(line))),
+        };
+        buffer.insert(at.0, at.1, &text);
+        let end = advance(at, &text);
+        (end.0, end.1.saturating_sub(1))
+    }
+}
+
+fn parse(keys: &[Key], mode: Mode) -> Parse {
+    let (count, rest) = take_count(keys);
+    let Some(&first) = rest.first() else {
+        return Parse::Incomplete;
+    };
+    let operator = match first {
+        Key::Char('d') => Some(Operator::Delete),
+        Key::Char('c') => Some(Operator::Change),
+        Key::Char('y') => Some(Operator::Yank),
+        _ => None,
+    };
+    if let Some(operator) = operator {
+        if mode == Mode::Visual {
+            return Parse::Done(count, Command::Operate(operator, None));
+        }
+        let (motion_count, rest) = take_count(&rest[1..]);
+        let count = match (count, motion_count) {
+            (Some(a), Some(b)) => Some(a * b),
+            (a, b) => a.or(b),
+        };
+        return match rest {
+            [] => Parse::Incomplete,
+            [key] if *key == first => Parse::Done(count, Command::Operate(operator, None)),
+            _ => match parse_motion(rest) {
+                Parse::Done(_, Command::Move(motion)) => {
+                    Parse::Done(count, Command::Operate(operator, Some(motion)))
+                }
+                other => other,
+            },
+        };
+    }
+    let command = match (first, mode) {
+        (Key::Char(c @ ('i' | 'a' | 'I' | 'A' | 'o' | 'O')), Mode::Normal) => Command::Insert(c),
+        (Key::Char('x'), _) => Command::DeleteChar,
+        (Key::Char('p'), Mode::Normal) => Command::Put { before: false },
+        (Key::Char('P'), Mode::Normal) => Command::Put { before: true },
+        (Key::Char('v'), _) => Command::Visual,
+        (Key::Char('.'), Mode::Normal) => Command::Repeat,
+        (Key::Char('g'), Mode::Normal) if matches!(rest, [_, Key::Char('-' | '+')]) => {
+            Command::Travel {
+                forward: rest[1] == Key::Char('+'),
+            }
+        }
+        _ => {
+            return match parse_motion(rest) {
+                Parse::Done(_, command) => Parse::Done(count, command),
+                other => other,
+            }
+        }
+    };
+    Parse::Done(count, command)
+}
+
+fn parse_motion(keys: &[Key]) -> Parse {
+    let motion = match keys {
+        [Key::Char('h')] | [Key::Backspace] => Motion::Left,
+        [Key::Char('l')] | [Key::Char(' ')] => Motion::Right,
+        [Key::Char('j')] | [Key::Enter] => Motion::Down,
+        [Key::Char('k')] => Motion::Up,
+        [Key::Char('w')] => Motion::WordForward,
+        [Key::Char('b')] => Motion::WordBackward,
+        [Key::Char('e')] => Motion::WordEnd,
+        [Key::Char('0')] => Motion::LineStart,
+        [Key::Char('$')] => Motion::LineEnd,
+        [Key::Char('G')] => Motion::LastLine,
+        [Key::Char('g')] | [Key::Char('f')] => return Parse::Incomplete,
+        [Key::Char('g'), Key::Char('g')] => Motion::FirstLine,
+        [Key::Char('f'), Key::Char(c)] => Motion::Find(*c),
+        _ => return Parse::Invalid,
+    };
+    Parse::Done(None, Command::Move(motion))
+}
+
+// a leading count, 0 on its own is the line start motion instead
+fn take_count(keys: &[Key]) -> (Option<usize>, &[Key]) {
+    let digits = keys
+        .iter()
+        .enumerate()
+        .take_while(|(index, key)| match key {
+            Key::Char('0') => *index > 0,
+            Key::Char(c) => c.is_ascii_digit(),
+            _ => false,
+        })
+        .count();
+    let count: String = keys[..digits]
+        .iter()
+        .filter_map(|key| match key {
+            Key::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect();
+    (count.parse().ok(), &keys[digits..])
+}
+
+// where a motion takes the cursor, None when it can't go anywhere
+fn motion_target(
+    buffer: &Buffer,
+    cursor: Pos,
+    motion: Motion,
+    count: Option<usize>,
+    for_operator: bool,
+    word_chars: &str,
+) -> Option<(Pos, Extent)> {
+    let times = count.unwrap_or(1);
+    let (line, column) = cursor;
+    let last_line = buffer.line_count() - 1;
+    let target = match motion {
+        Motion::Left => (
+            (line, column.saturating_sub(times as u32)),
+            Extent::Exclusive,
+        ),
+        Motion::Right => {
+            let column = (column + times as u32).min(buffer.line_len(line));
+            ((line, column), Extent::Exclusive)
+        }
+        Motion::Down | Motion::Up => {
+            let line = match motion {
+                Motion::Down => (line + times as u32).min(last_line),
+                _ => line.saturating_sub(times as u32),
+            };
+            ((line, column.min(buffer.line_len(line))), Extent::Linewise)
+        }
+        Motion::WordForward => {
+            let mut pos = cursor;
+            for _ in 0..times {
+                pos = word_forward(buffer, pos, word_chars);
+            }
+            // dw on the last word of a line doesn't join the next one on
+            if for_operator && pos.0 > line {
+                pos = (line, buffer.line_len(line));
+            }
+            (pos, Extent::Exclusive)
+        }
+        Motion::WordBackward => {
+            let mut pos = cursor;
+            for _ in 0..times {
+                pos = word_backward(buffer, pos, word_chars);
+            }
+            (pos, Extent::Exclusive)
+        }
+        Motion::WordEnd => {
+            let mut pos = cursor;
+            for _ in 0..times {
+                pos = word_end(buffer, pos, word_chars);
+            }
+            (pos, Extent::Inclusive)
+        }
+        Motion::LineStart => ((line, 0), Extent::Exclusive),
+        Motion::LineEnd => {
+            let line = (line + times as u32 - 1).min(last_line);
+            (
+                (line, buffer.line_len(line).saturating_sub(1)),
+                Extent::Inclusive,
+            )
+        }
+        Motion::FirstLine | Motion::LastLine => {
+            let line = match (motion, count) {
+                (_, Some(count)) => (count as u32).clamp(1, last_line + 1) - 1,
+                (Motion::FirstLine, None) => 0,
+                _ => last_line,
+            };
+            ((line, first_non_blank(buffer, line)), Extent::Linewise)
+        }
+        Motion::Find(target) => {
+            let text = line_text(buffer, line);
+            let column = text
+                .char_indices()
+                .filter(|(index, c)| *index > column as usize && *c == target)
+                .nth(times - 1)?
+                .0;
+            ((line, column as u32), Extent::Inclusive)
+        }
+    };
+    Some(target)
+}
+
+fn word_forward(buffer: &Buffer, start: Pos, word_chars: &str) -> Pos {
+    let mut pos = start;
+    let class = class_at(buffer, pos, word_chars);
+    if class != Class::Space {
+        while let Some(next) = next_pos(buffer, pos) {
+            pos = next;
+            if class_at(buffer, pos, word_chars) != class {
+                break;
+            }
+        }
+    }
+    while class_at(buffer, pos, word_chars) == Class::Space {
+        // an empty line counts as a word
+        if pos.0 != start.0 && buffer.line_len(pos.0) == 0 {
+            break;
+        }
+        match next_pos(buffer, pos) {
+            Some(next) => pos = next,
+            None => break,
+        }
+    }
+    pos
+}
+
+fn word_end(buffer: &Buffer, start: Pos, word_chars: &str) -> Pos {
+    let Some(mut pos) = next_pos(buffer, start) else {
+        return start;
+    };
+    while class_at(buffer, pos, word_chars) == Class::Space {
+        match next_pos(buffer, pos) {
+            Some(next) => pos = next,
+            None => return pos,
+        }
+    }
+    let class = class_at(buffer, pos, word_chars);
+    while let Some(next) = next_pos(buffer, pos) {
+        if class_at(buffer, next, word_chars) != class {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
+
+fn word_backward(buffer: &Buffer, start: Pos, word_chars: &str) -> Pos {
+    let Some(mut pos) = previous_pos(buffer, start) else {
+        return start;
+    };
+    while class_at(buffer, pos, word_chars) == Class::Space {
+        if buffer.line_len(pos.0) == 0 {
+            return pos;
+        }
+        match previous_pos(buffer, pos) {
+            Some(previous) => pos = previous,
+            None => return pos,
+        }
+    }
+    let class = class_at(buffer, pos, word_chars);
+    while let Some(previous) = previous_pos(buffer, pos) {
+        if class_at(buffer, previous, word_chars) != class {
+            break;
+        }
+        pos = previous;
+    }
+    pos
+}
+
+// the end of the word under the cursor for cw, later counts carry on like e
+fn change_word_end(buffer: &Buffer, start: Pos, count: usize, word_chars: &str) -> Pos {
+    let class = class_at(buffer, start, word_chars);
+    let mut pos = start;
+    while let Some(next) = next_pos(buffer, pos) {
+        if class_at(buffer, next, word_chars) != class {
+            break;
+        }
+        pos = next;
+    }
+    for _ in 1..count {
+        pos = word_end(buffer, pos, word_chars);
+    }
+    pos
+}
+
+fn class_at(buffer: &Buffer, pos: Pos, word_chars: &str) -> Class {
+    match line_text(buffer, pos.0).chars().nth(pos.1 as usize) {
+        Some(c) => motion::class(c, word_chars),
+        // past the end of a line is its newline
+        None => Class::Space,
+    }
+}
+
+// the column past the end of a line stands for its newline
+fn next_pos(buffer: &Buffer, (line, column): Pos) -> Option<Pos> {
+    if column < buffer.line_len(line) {
+        Some((line, column + 1))
+    } else if line + 1 < buffer.line_count() {
+        Some((line + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn previous_pos(buffer: &Buffer, (line, column): Pos) -> Option<Pos> {
+    if column > 0 {
+        Some((line, column - 1))
+    } else if line > 0 {
+        Some((line - 1, buffer.line_len(line - 1)))
+    } else {
+        None
+    }
+}
+
+fn line_text(buffer: &Buffer, line: u32) -> String {
+    buffer.get_lines(line, line + 1).pop().unwrap_or_default()
+}
+
+fn first_non_blank(buffer: &Buffer, line: u32) -> u32 {
+    line_text(buffer, line)
+        .chars()
+        .position(|c| !c.is_whitespace())
+        .unwrap_or(0) as u32
+}
+
+// normal mode sits on a character rather than after the last one
+fn clamp_normal(buffer: &Buffer, (line, column): Pos) -> Pos {
+    let line = line.min(buffer.line_count() - 1);
+    (line, column.min(buffer.line_len(line).saturating_sub(1)))
+}
+
+fn order(a: Pos, b: Pos) -> (Pos, Pos) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// where the cursor ends up after typing text at pos
+fn advance(pos: Pos, text: &str) -> Pos {
+    match text.rfind('\n') {
+        Some(index) => (
+            pos.0 + text.matches('\n').count() as u32,
+            text[index + 1..].chars().count() as u32,
+        ),
+        None => (pos.0, pos.1 + text.chars().count() as u32),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Key, Mode, Pos, Vim};
+    use crate::text_buffer::Buffer;
+    use std::path::PathBuf;
+
+    // types keys into a fresh buffer, <esc> stands for escape
+    fn run(text: &str, cursor: Pos, keys: &str) -> (String, Pos, Vim) {
+        let mut buffer = Buffer::from_text(PathBuf::from("test.txt"), text.as_bytes().to_vec());
+        let mut vim = Vim::new(false, "_");
+        let mut cursor = cursor;
+        for key in keys.replace("<esc>", "\x1b").chars() {
+            let key = match key {
+                '\x1b' => Key::Escape,
+                c => Key::Char(c),
+            };
+            cursor = vim.key(key, &mut buffer, cursor);
+        }
+        (buffer.get_lines(0, u32::MAX).join("\n"), cursor, vim)
+    }
+
+    #[test]
+    fn motions() {
+        let text = "fn main() {\n    let x = 1;\n}";
+        assert_eq!(run(text, (0, 0), "w").1, (0, 3));
+        assert_eq!(run(text, (0, 0), "2w").1, (0, 7));
+        assert_eq!(run(text, (0, 0), "e").1, (0, 1));
+        assert_eq!(run(text, (0, 3), "$").1, (0, 10));
+        assert_eq!(run(text, (0, 10), "w").1, (1, 4));
+        assert_eq!(run(text, (1, 4), "b").1, (0, 10));
+        assert_eq!(run(text, (1, 8), "0").1, (1, 0));
+        assert_eq!(run(text, (0, 5), "G").1, (2, 0));
+        assert_eq!(run(text, (2, 0), "gg").1, (0, 0));
+        assert_eq!(run(text, (0, 0), "2G").1, (1, 4));
+        assert_eq!(run(text, (1, 0), "f=").1, (1, 10));
+        assert_eq!(run(text, (0, 10), "jl").1, (1, 11));
+        assert_eq!(run(text, (0, 0), "fz").1, (0, 0));
+    }
+
+    #[test]
+    fn operators() {
+        let text = "one two three\nfour\nfive";
+        assert_eq!(run(text, (0, 0), "dw").0, "two three\nfour\nfive");
+        assert_eq!(run(text, (0, 0), "d2w").0, "three\nfour\nfive");
+        assert_eq!(run(text, (0, 0), "2dw").0, "three\nfour\nfive");
+        assert_eq!(run(text, (0, 8), "dw").0, "one two \nfour\nfive");
+        assert_eq!(run(text, (0, 4), "d$").0, "one \nfour\nfive");
+        assert_eq!(run(text, (0, 4), "dfe").0, "one e\nfour\nfive");
+        assert_eq!(run(text, (1, 0), "dd").0, "one two three\nfive");
+        assert_eq!(run(text, (2, 0), "dd").0, "one two three\nfour");
+        assert_eq!(run(text, (0, 0), "2dd").0, "five");
+        assert_eq!(run(text, (0, 0), "dj").0, "five");
+        assert_eq!(
+            run(text, (0, 4), "cwsix<esc>").0,
+            "one six three\nfour\nfive"
+        );
+        assert_eq!(
+            run(text, (1, 0), "ccsix<esc>").0,
+            "one two three\nsix\nfive"
+        );
+        assert_eq!(
+            run(text, (0, 0), "yyjp").0,
+            "one two three\nfour\none two three\nfive"
+        );
+        assert_eq!(run(text, (0, 0), "ywP").0, "one one two three\nfour\nfive");
+        assert_eq!(run(text, (2, 0), "xx").0, "one two three\nfour\nve");
+    }
+
+    #[test]
+    fn insert_and_repeat() {
+        let (text, cursor, vim) = run("abc", (0, 0), "Ax<esc>");
+        assert_eq!(
+            (text.as_str(), cursor, vim.mode()),
+            ("abcx", (0, 3), Mode::Normal)
+        );
+        assert_eq!(run("abc", (0, 1), "ix<esc>").0, "axbc");
+        assert_eq!(run("abc", (0, 0), "onew<esc>").0, "abc\nnew");
+        assert_eq!(run("abc", (0, 0), "Onew<esc>").0, "new\nabc");
+        assert_eq!(run("a b c d", (0, 0), "dw..").0, "d");
+        assert_eq!(run("a b c", (0, 0), "dwdwg-").0, "b c");
+        assert_eq!(run("a b c", (0, 0), "dwdw2g-g+").0, "b c");
+        assert_eq!(run("a b c", (0, 0), "dwg-.").0, "a c");
+        assert_eq!(run("a\nb\nc", (0, 0), "Ix<esc>j.j.").0, "xa\nxb\nxc");
+        let (_, _, vim) = run("abc", (0, 0), "i");
+        assert_eq!(vim.indicator(), "-- INSERT --");
+    }
+
+    #[test]
+    fn visual() {
+        let (_, cursor, vim) = run("one two", (0, 0), "vw");
+        assert_eq!(vim.selection(cursor), Some(((0, 0), (0, 5))));
+        assert_eq!(run("one two", (0, 0), "vwd").0, "wo");
+        assert_eq!(run("one two", (0, 4), "veyP").0, "one twotwo");
+        let (text, _, vim) = run("one two", (0, 0), "vecx<esc>");
+        assert_eq!((text.as_str(), vim.mode()), ("x two", Mode::Normal));
+    }
+}
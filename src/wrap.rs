@@ -0,0 +1,61 @@
+// where a line breaks into rows no wider than width, as the column each row
+// starts at. offsets are the x of every character boundary, the way
+// Atlas::layout_line gives them. Rows break after the last space that fits,
+// or wherever they run out of room when a word is wider than a whole row.
+// Spaces at the end of a row can hang past its edge
+pub fn row_starts(line: &str, offsets: &[i32], width: i32) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut start = 0;
+    // the column after the last space on the current row
+    let mut after_space = None;
+    for (column, character) in line.chars().enumerate() {
+        if character == ' ' || character == '\t' {
+            after_space = Some(column + 1);
+            continue;
+        }
+        // a character wider than the row still has to go somewhere
+        while column > start && offsets[column + 1] - offsets[start] > width {
+            let next = after_space
+                .filter(|after_space| *after_space > start)
+                .unwrap_or(column);
+            starts.push(next);
+            start = next;
+            after_space = None;
+        }
+    }
+    starts
+}
+
+// which of the rows a column is on
+pub fn row_of(starts: &[usize], column: usize) -> usize {
+    starts.partition_point(|start| *start <= column).max(1) - 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::{row_of, row_starts};
+
+    // every character 10 wide
+    fn rows(line: &str, width: i32) -> Vec<usize> {
+        let offsets: Vec<i32> = (0..=line.chars().count() as i32).map(|i| i * 10).collect();
+        row_starts(line, &offsets, width)
+    }
+
+    #[test]
+    fn breaks() {
+        assert_eq!(rows("", 50), vec![0]);
+        assert_eq!(rows("short", 50), vec![0]);
+        assert_eq!(rows("one two three", 80), vec![0, 8]);
+        // the space that doesn't fit hangs off the end of the row
+        assert_eq!(rows("abcd efgh", 40), vec![0, 5]);
+        assert_eq!(rows("abcdefghij", 40), vec![0, 4, 8]);
+        assert_eq!(rows("ab cdefghij", 40), vec![0, 3, 7]);
+        assert_eq!(rows("abc", 5), vec![0, 1, 2]);
+
+        let starts = rows("one two three", 80);
+        assert_eq!(row_of(&starts, 0), 0);
+        assert_eq!(row_of(&starts, 7), 0);
+        assert_eq!(row_of(&starts, 8), 1);
+        assert_eq!(row_of(&starts, 13), 1);
+    }
+}